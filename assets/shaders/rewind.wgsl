@@ -0,0 +1,30 @@
+#import bevy_sprite::mesh2d_view_bindings
+#import bevy_pbr::utils
+
+@group(1) @binding(0)
+var texture: texture_2d<f32>;
+
+@group(1) @binding(1)
+var our_sampler: sampler;
+
+@fragment
+fn fragment(
+    @builtin(position) position: vec4<f32>,
+    #import bevy_sprite::mesh2d_vertex_output
+) -> @location(0) vec4<f32> {
+    let uv = coords_to_viewport_uv(position.xy, view.viewport);
+
+    // Rows drift sideways like a tape being wound back
+    let wobble = 0.004 * sin(uv.y * 40.0 + globals.time * 25.0);
+    let color = textureSample(texture, our_sampler, uv + vec2<f32>(wobble, 0.0)).rgb;
+
+    // Washed out sepia
+    let gray = dot(color, vec3<f32>(0.299, 0.587, 0.114));
+    var sepia = vec3<f32>(gray * 1.07, gray * 0.95, gray * 0.78);
+
+    // Scanlines rolling upwards
+    let scanline = 0.85 + 0.15 * sin((uv.y + globals.time * 0.5) * 600.0);
+    sepia = sepia * scanline;
+
+    return vec4<f32>(sepia, 1.0);
+}
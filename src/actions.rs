@@ -0,0 +1,364 @@
+use std::ops::Deref;
+
+use bevy::input::InputSystem;
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+pub struct ActionPlugin;
+
+impl Plugin for ActionPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(PlayerBindings(vec![
+            load_bindings(
+                "assets/input/bindings.ron",
+                include_str!("../assets/input/bindings.ron"),
+            ),
+            load_bindings(
+                "assets/input/bindings_player_two.ron",
+                include_str!("../assets/input/bindings_player_two.ron"),
+            ),
+        ]))
+        .init_resource::<Input<Action>>()
+        .init_resource::<PlayerInputs>()
+        .add_system(
+            update_actions
+                .in_base_set(CoreSet::PreUpdate)
+                .after(InputSystem),
+        )
+        .add_system(
+            update_tick_actions
+                .before(PlayerMovementSet)
+                .in_schedule(CoreSchedule::FixedUpdate),
+        );
+    }
+}
+
+/// Reads bindings from disk so they can be changed without a rebuild, falling back to the embedded copy
+fn load_bindings(path: &str, embedded: &str) -> InputBindings {
+    #[cfg(not(target_arch = "wasm32"))]
+    match std::fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|text| ron::from_str::<InputBindings>(&text).map_err(|err| err.to_string()))
+    {
+        Ok(bindings) => return bindings,
+        Err(err) => warn!(
+            "Failed to load bindings {}, using the defaults: {}",
+            path, err
+        ),
+    }
+    #[cfg(target_arch = "wasm32")]
+    let _ = path;
+    ron::from_str::<InputBindings>(embedded).unwrap()
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    Jump,
+    Dash,
+    Interact,
+    Pause,
+    Grapple,
+    Rewind,
+}
+
+impl Action {
+    /// Bit order of replay masks. New actions go at the end, any change needs a new `REPLAY_FORMAT`.
+    pub const ALL: [Action; 10] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::Jump,
+        Action::Dash,
+        Action::Interact,
+        Action::Pause,
+        Action::Grapple,
+        Action::Rewind,
+    ];
+}
+
+//Pressed, just pressed and just released of every action share one u32 mask
+const _: () = assert!(3 * Action::ALL.len() <= 32);
+
+/// Swaps two actions in a mask from `TickActions::to_mask`
+pub fn swap_actions(mask: u32, a: Action, b: Action) -> u32 {
+    let count = Action::ALL.len();
+    let a = Action::ALL.iter().position(|action| *action == a).unwrap();
+    let b = Action::ALL.iter().position(|action| *action == b).unwrap();
+    let mut swapped = mask;
+    for group in 0..3 {
+        let (a, b) = (a + group * count, b + group * count);
+        swapped &= !(1 << a | 1 << b);
+        swapped |= (mask >> a & 1) << b | (mask >> b & 1) << a;
+    }
+    swapped
+}
+
+/// A player's actions as seen by the fixed timestep.
+/// Presses are held until a tick sees them so taps between ticks are not lost.
+#[derive(Component, Default)]
+pub struct TickActions {
+    input: Input<Action>,
+    pending: Vec<Action>,
+}
+
+impl TickActions {
+    /// Packs pressed, just pressed and just released into one bit set per tick, used by replays
+    pub fn to_mask(&self) -> u32 {
+        let count = Action::ALL.len();
+        let mut mask = 0;
+        for (i, action) in Action::ALL.into_iter().enumerate() {
+            if self.input.pressed(action) {
+                mask |= 1 << i;
+            }
+            if self.input.just_pressed(action) {
+                mask |= 1 << (i + count);
+            }
+            if self.input.just_released(action) {
+                mask |= 1 << (i + count * 2);
+            }
+        }
+        mask
+    }
+
+    pub fn set_mask(&mut self, mask: u32) {
+        let count = Action::ALL.len();
+        self.input.reset_all();
+        self.pending.clear();
+        for (i, action) in Action::ALL.into_iter().enumerate() {
+            if mask & 1 << (i + count * 2) != 0 {
+                self.input.press(action);
+                self.input.release(action);
+            }
+            if mask & 1 << i != 0 {
+                self.input.press(action);
+            }
+            if mask & 1 << (i + count) == 0 {
+                self.input.clear_just_pressed(action);
+            }
+        }
+    }
+}
+
+impl Deref for TickActions {
+    type Target = Input<Action>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.input
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    GamepadButton(GamepadButtonType),
+    /// Axis and whether the positive or negative side triggers the action
+    GamepadAxis(GamepadAxisType, bool),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct InputBindings {
+    pub deadzone: f32,
+    /// Only reads the nth connected gamepad, any gamepad when unset
+    #[serde(default)]
+    pub gamepad: Option<usize>,
+    pub actions: HashMap<Action, Vec<Binding>>,
+}
+
+/// Bindings for each local player, indexed by `PlayerIndex`
+#[derive(Resource, Clone)]
+pub struct PlayerBindings(pub Vec<InputBindings>);
+
+/// Held actions of each local player, indexed by `PlayerIndex`
+#[derive(Resource, Default)]
+pub struct PlayerInputs(pub Vec<Input<Action>>);
+
+impl PlayerBindings {
+    /// Bindings in use for each player. Playing alone takes every player's keys and any gamepad.
+    pub fn for_players(&self, co_op: &CoOpSettings) -> Vec<InputBindings> {
+        if co_op.enabled {
+            return self.0.clone();
+        }
+        let mut single = self.0[0].clone();
+        single.gamepad = None;
+        for other in &self.0[1..] {
+            for (action, action_bindings) in &other.actions {
+                let keys = action_bindings
+                    .iter()
+                    .filter(|binding| matches!(binding, Binding::Key(_)))
+                    .cloned();
+                single.actions.entry(*action).or_default().extend(keys);
+            }
+        }
+        vec![single]
+    }
+}
+
+impl InputBindings {
+    fn gamepads(&self, connected: &[Gamepad]) -> Vec<Gamepad> {
+        match self.gamepad {
+            Some(index) => connected.get(index).copied().into_iter().collect(),
+            None => connected.to_vec(),
+        }
+    }
+
+    fn active(
+        &self,
+        binding: &Binding,
+        keyboard: &Input<KeyCode>,
+        gamepads: &[Gamepad],
+        buttons: &Input<GamepadButton>,
+        axes: &Axis<GamepadAxis>,
+    ) -> bool {
+        match binding {
+            Binding::Key(key) => keyboard.pressed(*key),
+            Binding::GamepadButton(button_type) => gamepads
+                .iter()
+                .any(|gamepad| buttons.pressed(GamepadButton::new(*gamepad, *button_type))),
+            Binding::GamepadAxis(axis_type, positive) => gamepads.iter().any(|gamepad| {
+                let value = axes
+                    .get(GamepadAxis::new(*gamepad, *axis_type))
+                    .unwrap_or(0.0);
+                if *positive {
+                    value > self.deadzone
+                } else {
+                    value < -self.deadzone
+                }
+            }),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_actions(
+    bindings: Res<PlayerBindings>,
+    co_op: Res<CoOpSettings>,
+    mut active_bindings: Local<Vec<InputBindings>>,
+    keyboard: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    mut actions: ResMut<Input<Action>>,
+    mut inputs: ResMut<PlayerInputs>,
+    mut players: Query<(&PlayerIndex, &mut TickActions)>,
+) {
+    //Sorted so the nth gamepad stays the same between frames
+    let mut connected: Vec<Gamepad> = gamepads.iter().collect();
+    connected.sort_by_key(|gamepad| gamepad.id);
+
+    if bindings.is_changed() || co_op.is_changed() {
+        *active_bindings = bindings.for_players(&co_op);
+    }
+
+    inputs.0.resize_with(active_bindings.len(), default);
+    for (bindings, input) in active_bindings.iter().zip(&mut inputs.0) {
+        let gamepads = bindings.gamepads(&connected);
+        input.clear();
+        for (action, action_bindings) in &bindings.actions {
+            let active = action_bindings
+                .iter()
+                .any(|binding| bindings.active(binding, &keyboard, &gamepads, &buttons, &axes));
+            if active {
+                input.press(*action);
+            } else {
+                input.release(*action);
+            }
+        }
+    }
+
+    //Menus and pausing listen to every player
+    actions.clear();
+    for action in Action::ALL {
+        if inputs.0.iter().any(|input| input.pressed(action)) {
+            actions.press(action);
+        } else {
+            actions.release(action);
+        }
+    }
+
+    for (index, mut tick_actions) in &mut players {
+        if let Some(input) = inputs.0.get(index.0) {
+            tick_actions.pending.extend(input.get_just_pressed());
+        }
+    }
+}
+
+pub fn update_tick_actions(
+    inputs: Res<PlayerInputs>,
+    mut players: Query<(&PlayerIndex, &mut TickActions)>,
+) {
+    for (index, mut tick_actions) in &mut players {
+        let tick_actions = &mut *tick_actions;
+        let input = inputs.0.get(index.0);
+        tick_actions.input.clear();
+        for action in Action::ALL {
+            if input.is_some_and(|input| input.pressed(action))
+                || tick_actions.pending.contains(&action)
+            {
+                tick_actions.input.press(action);
+            } else {
+                tick_actions.input.release(action);
+            }
+        }
+        tick_actions.pending.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn held_jump_released_dash() -> TickActions {
+        let mut actions = TickActions::default();
+        actions.input.press(Action::MoveLeft);
+        actions.input.clear_just_pressed(Action::MoveLeft);
+        actions.input.press(Action::Jump);
+        actions.input.press(Action::Dash);
+        actions.input.release(Action::Dash);
+        actions
+    }
+
+    #[test]
+    fn mask_round_trips() {
+        let actions = held_jump_released_dash();
+        let mask = actions.to_mask();
+
+        let mut restored = TickActions::default();
+        restored.set_mask(mask);
+
+        assert_eq!(restored.to_mask(), mask);
+        assert!(restored.pressed(Action::MoveLeft));
+        assert!(!restored.just_pressed(Action::MoveLeft));
+        assert!(restored.just_pressed(Action::Jump));
+        assert!(restored.just_released(Action::Dash));
+        assert!(!restored.pressed(Action::Dash));
+    }
+
+    #[test]
+    fn swap_moves_every_group() {
+        let mask = held_jump_released_dash().to_mask();
+        let mut swapped = TickActions::default();
+        swapped.set_mask(swap_actions(mask, Action::Jump, Action::Dash));
+
+        assert!(swapped.just_pressed(Action::Dash));
+        assert!(swapped.just_released(Action::Jump));
+        assert!(!swapped.pressed(Action::Jump));
+        assert!(swapped.pressed(Action::MoveLeft));
+    }
+
+    #[test]
+    fn swapping_twice_is_a_no_op() {
+        let mask = held_jump_released_dash().to_mask();
+        let swapped = swap_actions(mask, Action::MoveLeft, Action::Rewind);
+        assert_ne!(swapped, mask);
+        assert_eq!(
+            swap_actions(swapped, Action::MoveLeft, Action::Rewind),
+            mask
+        );
+    }
+}
@@ -11,11 +11,17 @@ impl Plugin for AnimationPlugin {
     }
 }
 
-fn death_animation(mut deaths: Query<(&mut Transform, &Lifetime), With<DeathAnimation>>) {
-    for (mut transform, lifetime) in &mut deaths {
-        let angle = Lerp::lerp(&0.0, &(PI * 2.0), &lifetime.timer.percent());
-        let scale = Lerp::lerp(&1.0, &0.0, &lifetime.timer.percent());
-        transform.rotation = Quat::from_rotation_y(angle);
-        transform.scale.y = scale;
+//The lifetime is on the parent so the whole animation despawns together
+fn death_animation(
+    mut deaths: Query<(&mut Transform, &Parent), With<DeathAnimation>>,
+    lifetimes: Query<&Lifetime>,
+) {
+    for (mut transform, parent) in &mut deaths {
+        if let Ok(lifetime) = lifetimes.get(parent.get()) {
+            let angle = Lerp::lerp(&0.0, &(PI * 2.0), &lifetime.timer.percent());
+            let scale = Lerp::lerp(&1.0, &0.0, &lifetime.timer.percent());
+            transform.rotation = Quat::from_rotation_y(angle);
+            transform.scale.y = scale;
+        }
     }
 }
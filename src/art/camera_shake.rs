@@ -0,0 +1,78 @@
+use bevy::transform::TransformSystem;
+use rand::Rng;
+
+use crate::prelude::*;
+
+pub struct CameraShakePlugin;
+
+impl Plugin for CameraShakePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraShake>()
+            .add_system(add_trauma)
+            .add_system(
+                shake_camera
+                    .in_base_set(CoreSet::PostUpdate)
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+const MAX_SHAKE_OFFSET: f32 = 6.0;
+const TRAUMA_DECAY: f32 = 1.5;
+/// Landings slower than this don't shake the camera
+const HARD_LANDING_SPEED: f32 = 300.0;
+
+/// Shake strength from 0.0 to 1.0, the offset grows with the square so small bumps stay subtle
+#[derive(Resource, Default)]
+pub struct CameraShake {
+    pub trauma: f32,
+    offset: Vec2,
+    /// Camera position before shaking
+    base: Vec2,
+}
+
+impl CameraShake {
+    pub fn add_trauma(&mut self, trauma: f32) {
+        self.trauma = (self.trauma + trauma).min(1.0);
+    }
+}
+
+fn add_trauma(
+    mut shake: ResMut<CameraShake>,
+    mut landings: EventReader<LandedEvent>,
+    mut deaths: EventReader<PlayerDiedEvent>,
+) {
+    for landing in landings.iter() {
+        if landing.impact_speed > HARD_LANDING_SPEED {
+            shake.add_trauma((landing.impact_speed - HARD_LANDING_SPEED) / 600.0);
+        }
+    }
+    for _ in deaths.iter() {
+        shake.add_trauma(0.6);
+    }
+}
+
+fn shake_camera(
+    mut camera: Query<&mut Transform, With<MainCamera>>,
+    mut shake: ResMut<CameraShake>,
+    time: Res<Time>,
+) {
+    let mut rng = rand::thread_rng();
+    let strength = shake.trauma * shake.trauma * MAX_SHAKE_OFFSET;
+    let offset = Vec2::new(
+        rng.gen_range(-1.0..=1.0) * strength,
+        rng.gen_range(-1.0..=1.0) * strength,
+    );
+    for mut transform in &mut camera {
+        //Anything but the last shake moving the camera sets a new base, otherwise the old one is kept
+        let position = transform.translation.truncate();
+        if position != shake.base + shake.offset {
+            shake.base = position;
+        }
+        let shaken = shake.base + offset;
+        transform.translation.x = shaken.x;
+        transform.translation.y = shaken.y;
+    }
+    shake.offset = offset;
+    shake.trauma = (shake.trauma - TRAUMA_DECAY * time.delta_seconds()).max(0.0);
+}
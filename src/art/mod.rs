@@ -1,4 +1,5 @@
 mod animation;
+mod camera_shake;
 mod fade_in;
 mod particles;
 mod post_processing;
@@ -9,11 +10,13 @@ pub use sprite_sheets::*;
 
 use crate::prelude::*;
 
+pub use camera_shake::CameraShake;
 pub use fade_in::spawn_fadeout;
-pub use particles::spawn_new_rect_emitter;
+pub use particles::{spawn_new_rect_emitter, ParticleAtlas};
 pub use post_processing::*;
 
 use self::animation::AnimationPlugin;
+use self::camera_shake::CameraShakePlugin;
 use self::fade_in::FadeInPlugin;
 use self::particles::ParticlePlugin;
 use self::sprite_animation::SpriteAnimationPlugin;
@@ -25,6 +28,7 @@ impl Plugin for ArtPlugin {
         app.add_plugin(SpriteSheetPlugin)
             .add_plugin(ParticlePlugin)
             .add_plugin(AnimationPlugin)
+            .add_plugin(CameraShakePlugin)
             .add_plugin(SpriteAnimationPlugin)
             .add_plugin(FadeInPlugin)
             .add_plugin(PostProcessingPlugin)
@@ -123,6 +127,7 @@ pub struct CharacterBundle {
     #[bundle]
     pub sprite_sheet: SpriteSheetBundle,
     character: Character,
+    animation: CharacterAnimation,
 }
 
 #[derive(Bundle)]
@@ -132,8 +137,6 @@ pub struct IconBundle {
     icon: Icon,
 }
 
-pub const CHARACTER_SHEET_WIDTH: usize = 1;
-pub const CHARACTER_SHEET_HEIGHT: usize = 1;
 pub const ICON_SHEET_WIDTH: usize = 34;
 
 #[derive(Component, Clone, PartialEq, Eq, Hash, Default, Reflect)]
@@ -142,12 +145,88 @@ pub enum Icon {
     KeyE,
 }
 
-#[derive(Component, Clone, PartialEq, Eq, Hash, Default, Reflect, Serialize, Deserialize)]
+#[derive(
+    Component, Clone, PartialEq, Eq, Hash, Debug, Default, Reflect, Serialize, Deserialize,
+)]
 pub enum Character {
     #[default]
     Player,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+pub enum SpriteAnimation {
+    #[default]
+    Idle,
+    Run,
+    Jump,
+    Fall,
+    WallSlide,
+    Crouch,
+    Slide,
+    Death,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SpriteClip {
+    /// Indices into the character atlas
+    pub frames: Vec<usize>,
+    pub fps: f32,
+    /// Holds the last frame instead of looping
+    pub one_shot: bool,
+}
+
+/// Loaded from `assets/animations/characters.ron`
+#[derive(Resource, Clone, Serialize, Deserialize)]
+pub struct AnimationTable {
+    pub sheet: String,
+    pub tile_size: Vec2,
+    pub columns: usize,
+    pub rows: usize,
+    pub characters: HashMap<Character, HashMap<SpriteAnimation, SpriteClip>>,
+}
+
+impl AnimationTable {
+    /// Drops clips that can't be played, the character falls back to its still frame for those
+    pub fn remove_invalid_clips(&mut self) {
+        let tiles = self.columns * self.rows;
+        for (character, clips) in &mut self.characters {
+            clips.retain(|animation, clip| {
+                let problem = if clip.frames.is_empty() {
+                    Some("has no frames")
+                } else if !clip.fps.is_finite() || clip.fps <= 0.0 {
+                    Some("needs a positive fps")
+                } else if clip.frames.iter().any(|frame| *frame >= tiles) {
+                    Some("has a frame outside the sheet")
+                } else {
+                    None
+                };
+                if let Some(problem) = problem {
+                    error!("{:?} {:?} animation {}", character, animation, problem);
+                }
+                problem.is_none()
+            });
+        }
+    }
+}
+
+#[derive(Component, Default)]
+pub struct CharacterAnimation {
+    pub current: SpriteAnimation,
+    pub frame: usize,
+    pub elapsed: f32,
+}
+
+impl CharacterAnimation {
+    /// Restarts from the first frame only when the animation changes
+    pub fn play(&mut self, animation: SpriteAnimation) {
+        if self.current != animation {
+            self.current = animation;
+            self.frame = 0;
+            self.elapsed = 0.0;
+        }
+    }
+}
+
 #[derive(Resource)]
 pub struct SpriteSheetMaps {
     character_atlas: Handle<TextureAtlas>,
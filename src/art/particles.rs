@@ -6,7 +6,8 @@ pub struct ParticlePlugin;
 
 impl Plugin for ParticlePlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(particle_emitter_spawn)
+        app.add_startup_system(setup_particle_atlas)
+            .add_system(particle_emitter_spawn)
             .add_system(particles_lifetime)
             .add_system(particles_rotate)
             .add_system(particles_radial)
@@ -15,6 +16,21 @@ impl Plugin for ParticlePlugin {
     }
 }
 
+/// Atlas for `particles.png`, shared so emitters spawned during play don't each add their own
+#[derive(Resource)]
+pub struct ParticleAtlas(pub Handle<TextureAtlas>);
+
+fn setup_particle_atlas(
+    mut commands: Commands,
+    assets: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+) {
+    let texture_handle = assets.load("particles.png");
+    let texture_atlas =
+        TextureAtlas::from_grid(texture_handle, Vec2::new(16.0, 16.0), 1, 1, None, None);
+    commands.insert_resource(ParticleAtlas(texture_atlases.add(texture_atlas)));
+}
+
 pub fn spawn_new_rect_emitter(
     commands: &mut Commands,
     particle_desc: ParticleDesc,
@@ -25,7 +25,8 @@ impl Plugin for PostProcessingPlugin {
             .add_plugin(Material2dPlugin::<SpinnyMaterial>::default())
             .add_plugin(Material2dPlugin::<BlurMaterial>::default())
             .add_plugin(Material2dPlugin::<DistortionMaterial>::default())
-            .add_plugin(Material2dPlugin::<WeirdMaterial>::default());
+            .add_plugin(Material2dPlugin::<WeirdMaterial>::default())
+            .add_plugin(Material2dPlugin::<RewindMaterial>::default());
     }
 }
 
@@ -66,9 +67,9 @@ pub fn activate_post_processing(
         Query<&mut Visibility, With<Handle<SpinnyMaterial>>>,
     )>,
 ) {
-    if let Ok(fadeout) = fadeout.get_single() {
+    for fadeout in &fadeout {
         if !fadeout.fade_in_just_finished {
-            return;
+            continue;
         }
         match progression.current_map {
             0 => {
@@ -115,14 +116,21 @@ pub fn activate_post_processing(
 fn match_render_to_screen_size(
     mut texture: Query<&mut Transform, With<PostProcessingQuad>>,
     windows: Query<&Window>,
+    player: Query<&PotionEffects>,
 ) {
     let window = windows.single();
+    let mirror = if player.iter().any(|effects| effects.mirrored()) {
+        -1.0
+    } else {
+        1.0
+    };
     for mut texture in &mut texture {
-        texture.scale.x = window.resolution.width() / WIDTH;
+        texture.scale.x = mirror * window.resolution.width() / WIDTH;
         texture.scale.y = window.resolution.height() / HEIGHT;
     }
 }
 
+#[allow(dead_code)]
 fn toggle_chromatic(
     mut texture: Query<&mut Visibility, With<Handle<ChromaticAbrasionMaterial>>>,
     keyboard: Res<Input<KeyCode>>,
@@ -138,6 +146,7 @@ fn toggle_chromatic(
     }
 }
 
+#[allow(dead_code)]
 fn toggle_distort(
     mut texture: Query<&mut Visibility, With<Handle<DistortionMaterial>>>,
     keyboard: Res<Input<KeyCode>>,
@@ -153,6 +162,7 @@ fn toggle_distort(
     }
 }
 
+#[allow(dead_code)]
 fn toggle_wavy(
     mut texture: Query<&mut Visibility, With<Handle<SpinnyMaterial>>>,
     keyboard: Res<Input<KeyCode>>,
@@ -168,6 +178,7 @@ fn toggle_wavy(
     }
 }
 
+#[allow(dead_code)]
 fn toggle_weird(
     mut texture: Query<&mut Visibility, With<Handle<WeirdMaterial>>>,
     keyboard: Res<Input<KeyCode>>,
@@ -194,6 +205,7 @@ fn spawn_post_processing_textures(
     mut wavy_materials: ResMut<Assets<SpinnyMaterial>>,
     mut weird_materials: ResMut<Assets<WeirdMaterial>>,
     mut blur_materials: ResMut<Assets<BlurMaterial>>,
+    mut rewind_materials: ResMut<Assets<RewindMaterial>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
     let post_processing_pass_layer = RenderLayers::layer((RenderLayers::TOTAL_LAYERS - 1) as u8);
@@ -224,6 +236,10 @@ fn spawn_post_processing_textures(
         distortion_image: assets.load("weird.png"),
     });
 
+    let rewind_handle = rewind_materials.add(RewindMaterial {
+        source_image: image_handle.clone(),
+    });
+
     commands.spawn((
         MaterialMesh2dBundle {
             mesh: quad_handle.clone().into(),
@@ -304,6 +320,22 @@ fn spawn_post_processing_textures(
         Name::new("Post Processing Weird"),
     ));
 
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: quad_handle.clone().into(),
+            material: rewind_handle,
+            transform: Transform {
+                translation: Vec3::new(0.0, 0.0, 50.5),
+                ..default()
+            },
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        PostProcessingQuad,
+        post_processing_pass_layer,
+        Name::new("Post Processing Rewind"),
+    ));
+
     let material_handle = materials.add(ColorMaterial {
         texture: Some(image_handle),
         ..default()
@@ -400,3 +432,17 @@ impl Material2d for SpinnyMaterial {
         "shaders/wavy.wgsl".into()
     }
 }
+
+#[derive(AsBindGroup, TypeUuid, Clone)]
+#[uuid = "5c81d2a4-07e3-4b9f-9a1d-3e62f0c4b817"]
+pub struct RewindMaterial {
+    #[texture(0)]
+    #[sampler(1)]
+    pub source_image: Handle<Image>,
+}
+
+impl Material2d for RewindMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/rewind.wgsl".into()
+    }
+}
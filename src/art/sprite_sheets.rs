@@ -5,7 +5,36 @@ pub struct SpriteSheetPlugin;
 impl Plugin for SpriteSheetPlugin {
     fn build(&self, app: &mut App) {
         app.add_startup_system(setup_spritesheet_maps.in_base_set(StartupSet::PreStartup))
-            .add_system(update_art);
+            .add_system(animate_characters)
+            .add_system(update_art.after(animate_characters));
+    }
+}
+
+fn animate_characters(
+    mut characters: Query<(&Character, &mut CharacterAnimation)>,
+    table: Res<AnimationTable>,
+    time: Res<Time>,
+) {
+    for (character, mut animation) in &mut characters {
+        if let Some(clip) = table
+            .characters
+            .get(character)
+            .and_then(|clips| clips.get(&animation.current))
+        {
+            animation.elapsed += time.delta_seconds();
+            let frame_time = 1.0 / clip.fps;
+            while animation.elapsed >= frame_time {
+                animation.elapsed -= frame_time;
+                animation.frame += 1;
+            }
+            if animation.frame >= clip.frames.len() {
+                animation.frame = if clip.one_shot {
+                    clip.frames.len() - 1
+                } else {
+                    animation.frame % clip.frames.len()
+                };
+            }
+        }
     }
 }
 
@@ -14,12 +43,20 @@ fn update_art(
         &mut TextureAtlasSprite,
         &mut Handle<TextureAtlas>,
         &Character,
+        &CharacterAnimation,
     )>,
     sprite_sheets: Res<SpriteSheetMaps>,
+    table: Res<AnimationTable>,
 ) {
-    for (mut sprite, mut atlas, character) in &mut characters {
+    for (mut sprite, mut atlas, character, animation) in &mut characters {
         *atlas = sprite_sheets.character_atlas.clone();
-        sprite.index = sprite_sheets.characters[character];
+        sprite.index = table
+            .characters
+            .get(character)
+            .and_then(|clips| clips.get(&animation.current))
+            .and_then(|clip| clip.frames.get(animation.frame))
+            .copied()
+            .unwrap_or(sprite_sheets.characters[character]);
     }
 }
 
@@ -28,12 +65,17 @@ fn setup_spritesheet_maps(
     asset_server: Res<AssetServer>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
 ) {
-    let texture_handle = asset_server.load("player.png");
+    let mut table =
+        ron::from_str::<AnimationTable>(include_str!("../../assets/animations/characters.ron"))
+            .unwrap();
+    table.remove_invalid_clips();
+
+    let texture_handle = asset_server.load(table.sheet.as_str());
     let texture_atlas = TextureAtlas::from_grid(
         texture_handle,
-        Vec2::new(25.0, 31.0),
-        CHARACTER_SHEET_WIDTH,
-        CHARACTER_SHEET_HEIGHT,
+        table.tile_size,
+        table.columns,
+        table.rows,
         Some(Vec2::splat(1.0)),
         None,
     );
@@ -45,6 +87,7 @@ fn setup_spritesheet_maps(
         character_atlas,
         characters,
     });
+    commands.insert_resource(table);
 }
 
 impl Default for CharacterBundle {
@@ -59,6 +102,7 @@ impl Default for CharacterBundle {
                 ..Default::default()
             },
             character: Character::Player,
+            animation: CharacterAnimation::default(),
         }
     }
 }
@@ -0,0 +1,108 @@
+use crate::prelude::*;
+
+pub struct CoOpPlugin;
+
+impl Plugin for CoOpPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CoOpSettings>();
+    }
+}
+
+/// Replays pack each player's action mask into 32 bits, so this can't go above 2
+pub const MAX_PLAYERS: usize = 2;
+
+/// Multiplied into each player's sprite so they can be told apart
+pub const PLAYER_COLORS: [Color; MAX_PLAYERS] = [Color::WHITE, Color::rgb(1.0, 0.65, 0.55)];
+
+/// Space kept between the outermost players and the edge of the view
+const FRAMING_MARGIN: f32 = 96.0;
+/// Furthest the camera zooms out to keep everyone on screen
+const MAX_ZOOM: f32 = 1.75;
+
+#[derive(Resource, Default)]
+pub struct CoOpSettings {
+    /// Spawns a second player with their own bindings
+    pub enabled: bool,
+}
+
+impl CoOpSettings {
+    pub fn players(&self) -> usize {
+        if self.enabled {
+            MAX_PLAYERS
+        } else {
+            1
+        }
+    }
+}
+
+/// Camera position and projection scale that keep every player in view.
+/// The camera never zooms in past 1.0, past `MAX_ZOOM` players can leave the screen.
+pub fn frame_players(positions: &[Vec2], bounds: &MapBounds) -> Option<(Vec2, f32)> {
+    let first = *positions.first()?;
+    let (min, max) = positions
+        .iter()
+        .fold((first, first), |(min, max), position| {
+            (min.min(*position), max.max(*position))
+        });
+    let extent = max - min + Vec2::splat(FRAMING_MARGIN * 2.0);
+    let zoom = (extent.x / WIDTH)
+        .max(extent.y / HEIGHT)
+        .min(MAX_ZOOM)
+        .min(bounds.max_zoom())
+        .max(1.0);
+    Some((bounds.clamp_zoomed_camera((min + max) / 2.0, zoom), zoom))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounds() -> MapBounds {
+        MapBounds {
+            size: Vec2::new(4000.0, 2000.0),
+            floor: 0.0,
+        }
+    }
+
+    #[test]
+    fn no_players_no_framing() {
+        assert!(frame_players(&[], &bounds()).is_none());
+    }
+
+    #[test]
+    fn close_players_keep_the_normal_view() {
+        let (position, zoom) = frame_players(
+            &[Vec2::new(1000.0, 800.0), Vec2::new(1100.0, 800.0)],
+            &bounds(),
+        )
+        .unwrap();
+        assert_eq!(position, Vec2::new(1050.0, 800.0));
+        assert_eq!(zoom, 1.0);
+    }
+
+    #[test]
+    fn distant_players_zoom_out_up_to_the_limit() {
+        let (_, zoom) = frame_players(
+            &[Vec2::new(1000.0, 800.0), Vec2::new(1600.0, 800.0)],
+            &bounds(),
+        )
+        .unwrap();
+        assert!(zoom > 1.0 && zoom < MAX_ZOOM);
+
+        let (position, zoom) = frame_players(
+            &[Vec2::new(1000.0, 800.0), Vec2::new(3000.0, 800.0)],
+            &bounds(),
+        )
+        .unwrap();
+        assert_eq!(zoom, MAX_ZOOM);
+        assert_eq!(position, Vec2::new(2000.0, 800.0));
+    }
+
+    #[test]
+    fn framing_never_leaves_the_map() {
+        let (position, zoom) =
+            frame_players(&[Vec2::ZERO, Vec2::new(700.0, 0.0)], &bounds()).unwrap();
+        let half_view = Vec2::new(WIDTH, HEIGHT) * zoom / 2.0;
+        assert_eq!(position, half_view);
+    }
+}
@@ -95,14 +95,14 @@ fn enter_cutscene(
 fn close_dialog(
     mut commands: Commands,
     mut overworld_state: ResMut<NextState<GameState>>,
-    input: Res<Input<KeyCode>>,
+    actions: Res<Input<Action>>,
     dialog: Query<Entity, With<DialogUI>>,
     cutscene: ResMut<CutsceneTracker>,
 ) {
     if !cutscene.timer.finished() {
         return;
     }
-    if input.just_pressed(KeyCode::Space) {
+    if actions.just_pressed(Action::Interact) {
         for dialog in &dialog {
             commands.entity(dialog).despawn_recursive();
             overworld_state.set(GameState::Platforming);
@@ -0,0 +1,172 @@
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+pub struct GhostPlugin;
+
+impl Plugin for GhostPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GhostSettings>()
+            .init_resource::<GhostRuns>()
+            .init_resource::<GhostRecorder>()
+            .add_system(spawn_ghost.in_schedule(OnExit(GameState::Menu)))
+            .add_systems(
+                (record_ghost, save_ghost)
+                    .chain()
+                    .after(update_splits)
+                    .distributive_run_if(simulation_running)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(update_ghost);
+
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_startup_system(load_ghosts);
+    }
+}
+
+const GHOST_COLOR: Color = Color::rgba(0.6, 0.8, 1.0, 0.4);
+
+#[derive(Resource)]
+pub struct GhostSettings {
+    pub enabled: bool,
+}
+
+impl Default for GhostSettings {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// Player positions for every simulation tick of a finished level
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GhostRun {
+    pub time: f32,
+    pub positions: Vec<Vec2>,
+}
+
+/// Best run for each level, keyed by map index
+#[derive(Resource, Default)]
+pub struct GhostRuns(pub HashMap<usize, GhostRun>);
+
+#[derive(Resource, Default)]
+pub struct GhostRecorder {
+    level: usize,
+    positions: Vec<Vec2>,
+}
+
+#[derive(Component)]
+pub struct Ghost;
+
+#[cfg(not(target_arch = "wasm32"))]
+fn ghost_path(level: usize) -> String {
+    format!("ghosts/level_{}.ron", level + 1)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn load_ghosts(mut runs: ResMut<GhostRuns>, progression: Res<StoryProgression>) {
+    for level in 0..progression.levels.len() {
+        if let Ok(text) = std::fs::read_to_string(ghost_path(level)) {
+            match ron::from_str::<GhostRun>(&text) {
+                Ok(run) => {
+                    runs.0.insert(level, run);
+                }
+                Err(err) => warn!("Failed to load ghost for level {}: {}", level + 1, err),
+            }
+        }
+    }
+}
+
+fn spawn_ghost(mut commands: Commands) {
+    let mut bundle =
+        CharacterBundle::new(Vec3::new(0.0, 0.0, CHARACTER_Z - 1.0), Character::Player);
+    bundle.sprite_sheet.sprite.color = GHOST_COLOR;
+    bundle.sprite_sheet.visibility = Visibility::Hidden;
+    commands.spawn((bundle, Ghost, Name::new("Ghost")));
+}
+
+fn record_ghost(
+    player: Query<(&Transform, &PlayerIndex)>,
+    splits: Res<LevelSplits>,
+    mut recorder: ResMut<GhostRecorder>,
+) {
+    if recorder.level != splits.level {
+        recorder.level = splits.level;
+        recorder.positions.clear();
+    }
+    //Only player one races the ghost
+    for (transform, index) in &player {
+        if index.0 == 0 {
+            recorder.positions.push(transform.translation.truncate());
+        }
+    }
+}
+
+fn save_ghost(
+    mut splits: EventReader<LevelSplitEvent>,
+    recorder: Res<GhostRecorder>,
+    mut runs: ResMut<GhostRuns>,
+) {
+    for split in splits.iter() {
+        let time = split.time.as_secs_f32();
+        if runs
+            .0
+            .get(&split.level)
+            .is_some_and(|best| best.time <= time)
+        {
+            continue;
+        }
+        info!("New best for level {}: {:0.2}s", split.level + 1, time);
+        let run = GhostRun {
+            time,
+            positions: recorder.positions.clone(),
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let path = ghost_path(split.level);
+            let result = ron::to_string(&run)
+                .map_err(|err| err.to_string())
+                .and_then(|text| {
+                    std::fs::create_dir_all("ghosts").map_err(|err| err.to_string())?;
+                    std::fs::write(&path, text).map_err(|err| err.to_string())
+                });
+            if let Err(err) = result {
+                warn!("Failed to save ghost {}: {}", path, err);
+            }
+        }
+        runs.0.insert(split.level, run);
+    }
+}
+
+//Follows the best run at the same tick the player is on, blended like PhysicsInterpolation
+fn update_ghost(
+    mut ghost: Query<(&mut Transform, &mut Visibility, &mut TextureAtlasSprite), With<Ghost>>,
+    settings: Res<GhostSettings>,
+    runs: Res<GhostRuns>,
+    recorder: Res<GhostRecorder>,
+    fixed_time: Res<FixedTime>,
+) {
+    for (mut transform, mut visibility, mut sprite) in &mut ghost {
+        let tick = recorder.positions.len();
+        let run = runs
+            .0
+            .get(&recorder.level)
+            .filter(|run| settings.enabled && tick > 1 && tick <= run.positions.len());
+        if let Some(run) = run {
+            let previous = run.positions[tick - 2];
+            let current = run.positions[tick - 1];
+            let alpha =
+                (fixed_time.accumulated().as_secs_f32() / fixed_time.period.as_secs_f32()).min(1.0);
+            let position = previous.lerp(current, alpha);
+            transform.translation = position.extend(transform.translation.z);
+            if current.x > previous.x {
+                sprite.flip_x = true;
+            }
+            if current.x < previous.x {
+                sprite.flip_x = false;
+            }
+            *visibility = Visibility::Inherited;
+        } else {
+            *visibility = Visibility::Hidden;
+        }
+    }
+}
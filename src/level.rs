@@ -0,0 +1,246 @@
+use bevy::sprite::Anchor;
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+pub struct LevelPlugin;
+
+impl Plugin for LevelPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            (advance_auto_scroll, raise_hazards)
+                .after(RewindSet)
+                .before(PlayerMovementSet)
+                .distributive_run_if(simulation_running)
+                .distributive_run_if(not_rewinding)
+                .in_schedule(CoreSchedule::FixedUpdate),
+        )
+        .add_system(
+            chase_death
+                .in_set(PlayerCheckSet)
+                .run_if(simulation_running)
+                .in_schedule(CoreSchedule::FixedUpdate),
+        )
+        .add_system(
+            reset_chase
+                .after(PlayerMovementSet)
+                .before(PhysicsSet::SyncBackend)
+                .in_schedule(CoreSchedule::FixedUpdate),
+        )
+        .add_system(
+            auto_scroll_camera
+                .run_if(resource_exists::<AutoScroll>())
+                .in_set(OnUpdate(GameState::Platforming)),
+        )
+        .add_system(
+            auto_scroll_camera
+                .run_if(resource_exists::<AutoScroll>())
+                .in_set(OnUpdate(GameState::Cutscene)),
+        );
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct LevelSettings {
+    pub auto_scroll: Option<AutoScrollSettings>,
+    pub rising_hazard: Option<RisingHazardSettings>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AutoScrollSettings {
+    pub speed: f32,
+    /// Camera waypoints in world space, the camera starts at the first one
+    pub path: Vec<Vec2>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct RisingHazardSettings {
+    pub start_height: f32,
+    pub speed: f32,
+}
+
+#[derive(Resource)]
+pub struct AutoScroll {
+    pub settings: AutoScrollSettings,
+    pub distance: f32,
+}
+
+#[derive(Component)]
+pub struct RisingHazard {
+    pub settings: RisingHazardSettings,
+}
+
+const OFF_SCREEN_MARGIN: f32 = 16.0;
+
+impl AutoScroll {
+    /// Point on the path `distance` along it, empty paths are rejected when the level loads
+    pub fn position(&self) -> Vec2 {
+        let path = &self.settings.path;
+        let mut remaining = self.distance;
+        for segment in path.windows(2) {
+            let length = segment[0].distance(segment[1]);
+            if remaining <= length {
+                return segment[0].lerp(segment[1], remaining / length.max(f32::EPSILON));
+            }
+            remaining -= length;
+        }
+        path.last().copied().unwrap_or_default()
+    }
+}
+
+pub fn spawn_level_hazards(commands: &mut Commands, settings: &LevelSettings, bounds: &MapBounds) {
+    match &settings.auto_scroll {
+        Some(auto_scroll) if !auto_scroll.path.is_empty() => commands.insert_resource(AutoScroll {
+            settings: auto_scroll.clone(),
+            distance: 0.0,
+        }),
+        Some(_) => {
+            error!("Auto scroll needs at least one point in its path, the level won't scroll");
+            commands.remove_resource::<AutoScroll>();
+        }
+        None => commands.remove_resource::<AutoScroll>(),
+    }
+
+    if let Some(hazard) = settings.rising_hazard {
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::rgba(0.9, 0.3, 0.1, 0.85),
+                    custom_size: Some(Vec2::new(bounds.size.x, bounds.size.y + HEIGHT)),
+                    anchor: Anchor::TopLeft,
+                    ..default()
+                },
+                transform: Transform::from_xyz(0.0, hazard.start_height, PARTICLE_Z - 1.0),
+                ..default()
+            },
+            RisingHazard { settings: hazard },
+            Rewindable,
+            MapEntity,
+            Name::new("RisingHazard"),
+        ));
+    }
+}
+
+fn advance_auto_scroll(mut auto_scroll: Option<ResMut<AutoScroll>>, time: Res<FixedTime>) {
+    if let Some(auto_scroll) = auto_scroll.as_mut() {
+        auto_scroll.distance += auto_scroll.settings.speed * time.period.as_secs_f32();
+    }
+}
+
+fn auto_scroll_camera(
+    auto_scroll: Res<AutoScroll>,
+    bounds: Res<MapBounds>,
+    mut camera: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
+) {
+    let position = bounds.clamp_camera(auto_scroll.position());
+    let (mut camera, mut projection) = camera.single_mut();
+    camera.translation.x = position.x;
+    camera.translation.y = position.y;
+    //Off screen deaths are checked against the unzoomed view
+    if projection.scale != 1.0 {
+        projection.scale = 1.0;
+    }
+}
+
+fn raise_hazards(mut hazards: Query<(&mut Transform, &RisingHazard)>, time: Res<FixedTime>) {
+    for (mut transform, hazard) in &mut hazards {
+        transform.translation.y += hazard.settings.speed * time.period.as_secs_f32();
+    }
+}
+
+fn chase_death(
+    mut commands: Commands,
+    player: Query<
+        (Entity, &Transform),
+        (
+            With<PlayerStats>,
+            Without<RespawnTimer>,
+            Without<ReachedExit>,
+        ),
+    >,
+    hazards: Query<&Transform, With<RisingHazard>>,
+    auto_scroll: Option<Res<AutoScroll>>,
+    bounds: Res<MapBounds>,
+    settings: Res<DeathSettings>,
+    mut writer: EventWriter<PlayerDiedEvent>,
+) {
+    for (entity, transform) in &player {
+        let player = transform.translation;
+
+        let touched_hazard = hazards.iter().any(|hazard| player.y < hazard.translation.y);
+
+        //Uses the scroll path instead of the camera so deaths line up with the simulation
+        let off_screen = auto_scroll.as_ref().is_some_and(|auto_scroll| {
+            let view = bounds.clamp_camera(auto_scroll.position());
+            let offset = (player.truncate() - view).abs();
+            offset.x > WIDTH / 2.0 + OFF_SCREEN_MARGIN
+                || offset.y > HEIGHT / 2.0 + OFF_SCREEN_MARGIN
+        });
+
+        let cause = if touched_hazard {
+            DeathCause::Hazard
+        } else {
+            DeathCause::OffScreen
+        };
+        if touched_hazard || off_screen {
+            kill_player(
+                &mut commands,
+                &mut writer,
+                entity,
+                player.truncate(),
+                cause,
+                &settings,
+            );
+        }
+    }
+}
+
+fn reset_chase(
+    mut auto_scroll: Option<ResMut<AutoScroll>>,
+    mut hazards: Query<(&mut Transform, &RisingHazard)>,
+    mut respawns: EventReader<PlayerRespawnedEvent>,
+) {
+    if respawns.iter().count() == 0 {
+        return;
+    }
+    if let Some(auto_scroll) = auto_scroll.as_mut() {
+        auto_scroll.distance = 0.0;
+    }
+    for (mut transform, hazard) in &mut hazards {
+        transform.translation.y = hazard.settings.start_height;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scroll(path: Vec<Vec2>, distance: f32) -> AutoScroll {
+        AutoScroll {
+            settings: AutoScrollSettings { speed: 1.0, path },
+            distance,
+        }
+    }
+
+    #[test]
+    fn position_follows_the_path() {
+        let path = vec![Vec2::ZERO, Vec2::new(100.0, 0.0), Vec2::new(100.0, 50.0)];
+        assert_eq!(scroll(path.clone(), 0.0).position(), Vec2::ZERO);
+        assert_eq!(scroll(path.clone(), 40.0).position(), Vec2::new(40.0, 0.0));
+        assert_eq!(
+            scroll(path.clone(), 125.0).position(),
+            Vec2::new(100.0, 25.0)
+        );
+        //Stops at the end of the path
+        assert_eq!(scroll(path, 1000.0).position(), Vec2::new(100.0, 50.0));
+    }
+
+    #[test]
+    fn position_handles_short_paths() {
+        let point = Vec2::new(3.0, 4.0);
+        assert_eq!(scroll(vec![point], 10.0).position(), point);
+        assert_eq!(scroll(vec![point, point], 0.0).position(), point);
+        assert_eq!(scroll(Vec::new(), 10.0).position(), Vec2::ZERO);
+    }
+}
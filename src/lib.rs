@@ -1,9 +1,18 @@
 #![allow(clippy::type_complexity)]
+mod actions;
 mod art;
+mod co_op;
 mod cutscene;
+mod ghost;
+mod level;
 mod map;
 mod menu;
 mod player;
+mod player_state;
+mod potion;
+mod replay;
+mod rewind;
+mod simulation;
 mod timer;
 mod utils;
 
@@ -12,11 +21,20 @@ pub mod prelude {
     pub const HEIGHT: f32 = 480.0;
     pub const RESOLUTION: f32 = WIDTH / HEIGHT;
 
+    pub use crate::actions::*;
     pub use crate::art::*;
+    pub use crate::co_op::*;
     pub use crate::cutscene::*;
+    pub use crate::ghost::*;
+    pub use crate::level::*;
     pub use crate::map::*;
     pub use crate::menu::*;
     pub use crate::player::*;
+    pub use crate::player_state::*;
+    pub use crate::potion::*;
+    pub use crate::replay::*;
+    pub use crate::rewind::*;
+    pub use crate::simulation::*;
     pub use crate::timer::*;
     pub use crate::utils::*;
 
@@ -35,6 +53,26 @@ pub mod prelude {
 
     pub struct DisableEffectsEvent;
     pub struct JumpEvent;
+    pub struct AirJumpEvent;
+    pub struct DashEvent;
+    pub struct WallSlideEvent;
+    pub struct GlideEvent;
+    pub struct PlayerRespawnedEvent;
+    pub struct LandedEvent {
+        pub player: Entity,
+        /// Speed towards the ground on impact
+        pub impact_speed: f32,
+    }
+    pub struct WallJumpEvent;
+    pub struct PlayerDiedEvent {
+        pub player: Entity,
+        pub cause: DeathCause,
+        pub position: Vec2,
+    }
+    pub struct PotionDrunkEvent {
+        pub level: usize,
+    }
+    pub struct LevelExitedEvent;
 
     #[derive(Resource)]
     pub struct MainRender(pub Handle<Image>);
@@ -44,6 +82,7 @@ pub mod prelude {
         #[default]
         Menu,
         Platforming,
+        Paused,
         Cutscene,
         Win,
     }
@@ -56,6 +95,14 @@ pub mod prelude {
     #[derive(Component)]
     pub struct Door;
 
+    #[derive(Component)]
+    pub struct Ladder;
+
+    #[derive(Component)]
+    pub struct Portal {
+        pub exit: Vec2,
+    }
+
     #[derive(Component)]
     pub struct PotionFade(pub usize);
 
@@ -64,9 +111,10 @@ pub mod prelude {
         pub story_marker: usize,
         pub current_map: usize,
         pub respawn_point: Vec3,
+        pub potion_respawn_point: Vec3,
         pub respawn_alt: bool,
-        pub potion_spawns: Vec<Vec2>,
         pub levels: Vec<String>,
-        pub potion_effects: Vec<PlayerStats>,
+        pub potion_effects: Vec<PotionEffect>,
+        pub level_settings: Vec<LevelSettings>,
     }
 }
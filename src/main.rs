@@ -1,11 +1,7 @@
-use bevy::{
-    input::common_conditions::input_toggle_active,
-    render::{
-        camera::ScalingMode,
-        render_resource::{AddressMode, FilterMode, SamplerDescriptor},
-    },
+use bevy::render::{
+    camera::ScalingMode,
+    render_resource::{AddressMode, FilterMode, SamplerDescriptor},
 };
-use bevy_inspector_egui::quick::WorldInspectorPlugin;
 use logic_overdosed::prelude::*;
 
 use bevy::render::{
@@ -23,17 +19,22 @@ fn main() {
     app.add_state::<GameState>()
         .add_event::<DisableEffectsEvent>()
         .add_event::<JumpEvent>()
+        .add_event::<AirJumpEvent>()
+        .add_event::<DashEvent>()
+        .add_event::<WallSlideEvent>()
+        .add_event::<GlideEvent>()
+        .add_event::<PlayerRespawnedEvent>()
+        .add_event::<LandedEvent>()
+        .add_event::<WallJumpEvent>()
+        .add_event::<PlayerDiedEvent>()
+        .add_event::<PotionDrunkEvent>()
+        .add_event::<LevelExitedEvent>()
         .insert_resource(StoryProgression {
             story_marker: 0,
             respawn_point: Vec3::new(55.0, 50.0, CHARACTER_Z),
+            potion_respawn_point: Vec3::new(55.0, 50.0, CHARACTER_Z),
             current_map: 0,
             respawn_alt: false,
-            potion_spawns: vec![
-                Vec2::new(55.0, 50.0),
-                Vec2::new(2440.0, 140.0),
-                Vec2::new(55.0, 50.0),
-                Vec2::new(55.0, 50.0),
-            ],
             levels: vec![
                 //TODO find better way to handle this that also works on web
                 include_str!("../assets/maps/map_1.map").to_string(),
@@ -42,16 +43,27 @@ fn main() {
                 include_str!("../assets/maps/map_4.map").to_string(),
             ],
             potion_effects: vec![
-                ron::from_str::<PlayerStats>(include_str!("../assets/potions/level_1.ron"))
+                ron::from_str::<PotionEffect>(include_str!("../assets/potions/level_1.ron"))
                     .unwrap(),
-                ron::from_str::<PlayerStats>(include_str!("../assets/potions/level_2.ron"))
+                ron::from_str::<PotionEffect>(include_str!("../assets/potions/level_2.ron"))
                     .unwrap(),
-                ron::from_str::<PlayerStats>(include_str!("../assets/potions/level_3.ron"))
+                ron::from_str::<PotionEffect>(include_str!("../assets/potions/level_3.ron"))
                     .unwrap(),
-                ron::from_str::<PlayerStats>(include_str!("../assets/potions/level_4.ron"))
+                ron::from_str::<PotionEffect>(include_str!("../assets/potions/level_4.ron"))
+                    .unwrap(),
+            ],
+            level_settings: vec![
+                ron::from_str::<LevelSettings>(include_str!("../assets/levels/level_1.ron"))
+                    .unwrap(),
+                ron::from_str::<LevelSettings>(include_str!("../assets/levels/level_2.ron"))
+                    .unwrap(),
+                ron::from_str::<LevelSettings>(include_str!("../assets/levels/level_3.ron"))
+                    .unwrap(),
+                ron::from_str::<LevelSettings>(include_str!("../assets/levels/level_4.ron"))
                     .unwrap(),
             ],
         })
+        .init_resource::<MapBounds>()
         .insert_resource(ClearColor(Color::BLACK))
         .add_plugins(
             DefaultPlugins
@@ -80,24 +92,71 @@ fn main() {
         //.add_plugin(
         //WorldInspectorPlugin::default().run_if(input_toggle_active(false, KeyCode::Escape)),
         //)
-        .add_plugin(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(50.0))
+        .add_plugin(
+            RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(50.0)
+                .with_default_system_setup(false),
+        )
+        .add_plugin(SimulationPlugin)
         //.add_plugin(RapierDebugRenderPlugin::default())
-        .add_system(setup_player.in_schedule(OnExit(GameState::Menu)))
+        .add_system(
+            setup_player
+                .after(setup_default_map)
+                .in_schedule(OnExit(GameState::Menu)),
+        )
         .add_system(update_lifetimes.in_base_set(CoreSet::PostUpdate))
         .add_startup_system(setup_camera)
         .add_system(setup_default_map.in_schedule(OnExit(GameState::Menu)))
-        .add_system(camera_updating.in_set(OnUpdate(GameState::Platforming)))
-        .add_system(camera_updating.in_set(OnUpdate(GameState::Cutscene)))
+        .add_system(
+            camera_updating
+                .run_if(not(resource_exists::<AutoScroll>()))
+                .in_set(OnUpdate(GameState::Platforming)),
+        )
+        .add_system(
+            camera_updating
+                .run_if(not(resource_exists::<AutoScroll>()))
+                .in_set(OnUpdate(GameState::Cutscene)),
+        )
+        .add_plugin(ActionPlugin)
         .add_plugin(PlayerPlugin)
+        .add_plugin(PlayerStatePlugin)
+        .add_plugin(PotionPlugin)
         .add_plugin(DialogPlugin)
         .add_plugin(MapPlugin)
+        .add_plugin(LevelPlugin)
         .add_plugin(MenuPlugin)
         .add_plugin(SpeedrunPlugin)
+        .add_plugin(ReplayPlugin)
+        .add_plugin(CoOpPlugin)
+        .add_plugin(RewindPlugin)
+        .add_plugin(GhostPlugin)
         .add_plugin(AudioPlugin)
         .add_system(start_background_audio.on_startup())
         .add_system(jump_audio)
+        .add_system(dash_audio)
+        .add_system(slide_audio)
+        .add_system(land_audio)
+        .add_system(death_audio)
+        .add_system(potion_audio)
         .add_plugin(ArtPlugin);
 
+    //Replays are played back with `--replay <path>`
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(path) = args
+        .iter()
+        .position(|arg| arg == "--replay")
+        .and_then(|i| args.get(i + 1))
+    {
+        match Replay::load(path) {
+            Ok(replay) => {
+                app.insert_resource(CoOpSettings {
+                    enabled: replay.co_op,
+                });
+                app.insert_resource(ReplayPlayback::new(replay));
+            }
+            Err(err) => error!("Failed to load replay {}: {}", path, err),
+        }
+    }
+
     app.run();
 }
 
@@ -105,9 +164,11 @@ fn jump_audio(
     asset_server: Res<AssetServer>,
     audio: Res<Audio>,
     mut reader: EventReader<JumpEvent>,
+    mut air_jumps: EventReader<AirJumpEvent>,
+    mut wall_jumps: EventReader<WallJumpEvent>,
 ) {
     //XXX use run condition
-    if reader.iter().count() == 0 {
+    if reader.iter().count() + air_jumps.iter().count() + wall_jumps.iter().count() == 0 {
         return;
     }
     audio
@@ -115,6 +176,78 @@ fn jump_audio(
         .with_volume(0.4);
 }
 
+fn dash_audio(
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    mut reader: EventReader<DashEvent>,
+) {
+    if reader.iter().count() == 0 {
+        return;
+    }
+    audio
+        .play(asset_server.load("audio/dash.wav"))
+        .with_volume(0.4);
+}
+
+//Played when a wall slide or glide starts
+fn slide_audio(
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    mut wall_slides: EventReader<WallSlideEvent>,
+    mut glides: EventReader<GlideEvent>,
+) {
+    if wall_slides.iter().count() + glides.iter().count() == 0 {
+        return;
+    }
+    audio
+        .play(asset_server.load("audio/slide.wav"))
+        .with_volume(0.3);
+}
+
+fn land_audio(
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    mut reader: EventReader<LandedEvent>,
+) {
+    for landing in reader.iter() {
+        //Small hops stay silent, big falls thud louder
+        if landing.impact_speed < LANDING_SOUND_SPEED {
+            continue;
+        }
+        audio
+            .play(asset_server.load("audio/land.wav"))
+            .with_volume((landing.impact_speed as f64 / 1000.0).min(0.5));
+    }
+}
+
+const LANDING_SOUND_SPEED: f32 = 150.0;
+
+fn death_audio(
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    mut reader: EventReader<PlayerDiedEvent>,
+) {
+    if reader.iter().count() == 0 {
+        return;
+    }
+    audio
+        .play(asset_server.load("audio/death.wav"))
+        .with_volume(0.5);
+}
+
+fn potion_audio(
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    mut reader: EventReader<PotionDrunkEvent>,
+) {
+    if reader.iter().count() == 0 {
+        return;
+    }
+    audio
+        .play(asset_server.load("audio/potion.wav"))
+        .with_volume(0.4);
+}
+
 fn start_background_audio(asset_server: Res<AssetServer>, audio: Res<Audio>) {
     audio
         .play(asset_server.load("audio/background.wav"))
@@ -126,23 +259,34 @@ fn start_background_audio(asset_server: Res<AssetServer>, audio: Res<Audio>) {
         .looped();
 }
 
+//Zooms out to keep every player in view, players waiting at the exit are left out
+#[allow(clippy::type_complexity)]
 fn camera_updating(
-    player: Query<&Transform, (With<PlayerVelocity>, Without<MainCamera>)>,
-    mut camera: Query<&mut Transform, With<MainCamera>>,
+    player: Query<(&Transform, Option<&ReachedExit>), (With<PlayerVelocity>, Without<MainCamera>)>,
+    mut camera: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
+    bounds: Res<MapBounds>,
 ) {
-    if let Ok(player) = player.get_single() {
-        let mut camera = camera.single_mut();
-        camera.translation.x = player.translation.x;
-        camera.translation.x = camera.translation.x.clamp(WIDTH / 2.0, WIDTH * 3.5);
+    let positions: Vec<Vec2> = player
+        .iter()
+        .filter(|(_, waiting)| waiting.is_none())
+        .map(|(player, _)| player.translation.truncate())
+        .collect();
+    if let Some((position, zoom)) = frame_players(&positions, &bounds) {
+        let (mut camera, mut projection) = camera.single_mut();
+        camera.translation.x = position.x;
+        camera.translation.y = position.y;
+        if projection.scale != zoom {
+            projection.scale = zoom;
+        }
     }
 }
 
 fn setup_default_map(
     mut commands: Commands,
     assets: Res<AssetServer>,
-    progression: Res<StoryProgression>,
+    mut progression: ResMut<StoryProgression>,
 ) {
-    load_map(&mut commands, &assets, &progression);
+    load_map(&mut commands, &assets, &mut progression);
 }
 
 fn setup_camera(
@@ -206,105 +350,134 @@ fn setup_camera(
 fn setup_player(
     mut commands: Commands,
     assets: Res<AssetServer>,
+    progression: Res<StoryProgression>,
+    co_op: Res<CoOpSettings>,
+    particle_atlas: Res<ParticleAtlas>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
 ) {
+    let stats =
+        ron::from_str::<PlayerStats>(include_str!("../assets/potions/default_player.ron")).unwrap();
+
     let texture_handle = assets.load("smoke_particles.png");
     let texture_atlas =
         TextureAtlas::from_grid(texture_handle, Vec2::new(32.0, 32.0), 1, 1, None, None);
-    let particle_atlas = texture_atlases.add(texture_atlas);
+    let smoke_atlas = texture_atlases.add(texture_atlas);
 
-    let feet_particle_emitter = spawn_new_rect_emitter(
-        &mut commands,
-        ParticleDesc {
-            particle: Particle {
-                lifetime: Timer::from_seconds(0.4, TimerMode::Once),
-            },
-            sprite: SpriteSheetBundle {
-                sprite: TextureAtlasSprite {
-                    custom_size: Some(Vec2::splat(12.0)),
+    for (index, color) in PLAYER_COLORS.iter().enumerate().take(co_op.players()) {
+        let feet_particle_emitter = spawn_new_rect_emitter(
+            &mut commands,
+            ParticleDesc {
+                particle: Particle {
+                    lifetime: Timer::from_seconds(0.4, TimerMode::Once),
+                },
+                sprite: SpriteSheetBundle {
+                    sprite: TextureAtlasSprite {
+                        custom_size: Some(Vec2::splat(12.0)),
+                        ..default()
+                    },
+                    texture_atlas: smoke_atlas.clone(),
                     ..default()
                 },
-                texture_atlas: particle_atlas,
-                ..default()
-            },
-            falling: Some(FallingParticle { speed: 12.0 }),
-            radial: Some(RadialParticle {
-                speed: 16.0,
-                direction: Vec2::ZERO,
-            }),
-            rotating: Some(RotatingParticle { speed: 24.0 }),
-            fading: Some(FadingParticle {}),
-        },
-        Vec2::new(0.0, -13.0),
-        Vec2::new(7.0, 3.0),
-        None,
-        1,
-        None,
-    );
-
-    commands
-        .entity(feet_particle_emitter)
-        .insert(PlayerFeetParticles);
-
-    let texture_handle = assets.load("particles.png");
-    let texture_atlas =
-        TextureAtlas::from_grid(texture_handle, Vec2::new(16.0, 16.0), 1, 1, None, None);
-    let particle_atlas = texture_atlases.add(texture_atlas);
-
-    let head_particle_emitter = spawn_new_rect_emitter(
-        &mut commands,
-        ParticleDesc {
-            particle: Particle {
-                lifetime: Timer::from_seconds(0.4, TimerMode::Once),
+                falling: Some(FallingParticle { speed: 12.0 }),
+                radial: Some(RadialParticle {
+                    speed: 16.0,
+                    direction: Vec2::ZERO,
+                }),
+                rotating: Some(RotatingParticle { speed: 24.0 }),
+                fading: Some(FadingParticle {}),
             },
-            sprite: SpriteSheetBundle {
-                sprite: TextureAtlasSprite {
-                    custom_size: Some(Vec2::splat(8.0)),
+            Vec2::new(0.0, -13.0),
+            Vec2::new(7.0, 3.0),
+            None,
+            1,
+            None,
+        );
+
+        commands
+            .entity(feet_particle_emitter)
+            .insert(PlayerFeetParticles);
+
+        let head_particle_emitter = spawn_new_rect_emitter(
+            &mut commands,
+            ParticleDesc {
+                particle: Particle {
+                    lifetime: Timer::from_seconds(0.4, TimerMode::Once),
+                },
+                sprite: SpriteSheetBundle {
+                    sprite: TextureAtlasSprite {
+                        custom_size: Some(Vec2::splat(8.0)),
+                        ..default()
+                    },
+                    texture_atlas: particle_atlas.0.clone(),
                     ..default()
                 },
-                texture_atlas: particle_atlas,
-                ..default()
-            },
-            falling: Some(FallingParticle { speed: 12.0 }),
-            radial: Some(RadialParticle {
-                speed: 14.0,
-                direction: Vec2::ZERO,
-            }),
-            rotating: Some(RotatingParticle { speed: 6.0 }),
-            fading: Some(FadingParticle {}),
-        },
-        Vec2::new(0.0, 13.0),
-        Vec2::new(16.0, 4.0),
-        None,
-        1,
-        None,
-    );
-
-    commands
-        .entity(head_particle_emitter)
-        .insert(PlayerHeadParticles);
-
-    commands
-        .spawn((
-            CharacterBundle::new(Vec3::new(55.0, 50.0, CHARACTER_Z), Character::Player),
-            RigidBody::KinematicPositionBased,
-            //Collider::capsule(Vec2::new(0.0, -6.3), Vec2::new(0.0, 2.5), 20.0 / 2.0),
-            Collider::cuboid(17.0 / 2.0, 28.0 / 2.0),
-            PlayerVelocity {
-                velocity: Vec2::ZERO,
-                on_wall: OnWall::NotOnWall,
-                last_on_wall: 0,
-            },
-            KinematicCharacterController {
-                filter_flags: QueryFilterFlags::EXCLUDE_SENSORS,
-                ..default()
+                falling: Some(FallingParticle { speed: 12.0 }),
+                radial: Some(RadialParticle {
+                    speed: 14.0,
+                    direction: Vec2::ZERO,
+                }),
+                rotating: Some(RotatingParticle { speed: 6.0 }),
+                fading: Some(FadingParticle {}),
             },
-            ron::from_str::<PlayerStats>(include_str!("../assets/potions/default_player.ron"))
-                .unwrap(),
-            Name::new("Player"),
-        ))
-        .add_child(head_particle_emitter)
-        .add_child(feet_particle_emitter);
+            Vec2::new(0.0, 13.0),
+            Vec2::new(16.0, 4.0),
+            None,
+            1,
+            None,
+        );
+
+        commands
+            .entity(head_particle_emitter)
+            .insert(PlayerHeadParticles);
+
+        let mut character = CharacterBundle::new(progression.respawn_point, Character::Player);
+        character.sprite_sheet.sprite.color = *color;
+
+        commands
+            .spawn((
+                character,
+                PhysicsInterpolation::new(progression.respawn_point),
+                RigidBody::KinematicPositionBased,
+                //Collider::capsule(Vec2::new(0.0, -6.3), Vec2::new(0.0, 2.5), 20.0 / 2.0),
+                (player_collider(false), player_collision_groups()),
+                PlayerState::default(),
+                PlayerVelocity {
+                    velocity: Vec2::ZERO,
+                    on_wall: OnWall::NotOnWall,
+                    time_since_wall: 0.0,
+                    climbing: false,
+                    coyote_timer: 0.0,
+                    jump_buffer: 0.0,
+                    air_jumps_used: 0,
+                    dash_timer: 0.0,
+                    dash_cooldown: 0.0,
+                    dash_direction: Vec2::X,
+                    wall_sliding: false,
+                    gliding: false,
+                    crouching: false,
+                    slide_timer: 0.0,
+                    grapple: None,
+                    grapple_length: 0.0,
+                    grapple_cooldown: 0.0,
+                    swing_momentum: false,
+                },
+                KinematicCharacterController {
+                    filter_flags: QueryFilterFlags::EXCLUDE_SENSORS,
+                    filter_groups: Some(player_filter_groups()),
+                    //Crates are pushed by player_push using the player stats
+                    apply_impulse_to_dynamic_bodies: false,
+                    ..default()
+                },
+                stats,
+                PotionEffects::new(stats),
+                Squash::default(),
+                Lean::default(),
+                (PlayerIndex(index), TickActions::default(), Rewindable),
+                Name::new(format!("Player {}", index + 1)),
+            ))
+            .add_child(head_particle_emitter)
+            .add_child(feet_particle_emitter);
+    }
 
     /*
     commands.spawn((
@@ -3,12 +3,147 @@ use crate::prelude::*;
 pub struct MapPlugin;
 
 impl Plugin for MapPlugin {
-    fn build(&self, _app: &mut App) {}
+    fn build(&self, app: &mut App) {
+        //Gates change collision so they open on the same tick at any frame rate
+        app.add_systems(
+            (update_pressure_plates, update_gates)
+                .chain()
+                .after(PlayerMovementSet)
+                .before(PhysicsSet::SyncBackend)
+                .distributive_run_if(simulation_running)
+                .in_schedule(CoreSchedule::FixedUpdate),
+        )
+        .add_system(
+            reset_crates
+                .after(PlayerMovementSet)
+                .before(PhysicsSet::SyncBackend)
+                .in_schedule(CoreSchedule::FixedUpdate),
+        );
+    }
 }
 
+pub const TILE_SIZE: f32 = 32.0;
+pub const KILL_PLANE_DEPTH: f32 = 96.0;
+pub const CRATE_SIZE: f32 = 30.0;
+pub const CRATE_MASS: f32 = 1.0;
+
 #[derive(Component)]
 pub struct MapEntity;
 
+#[derive(Resource, Default)]
+pub struct MapBounds {
+    pub size: Vec2,
+    /// Bottom of the lowest solid row
+    pub floor: f32,
+}
+
+impl MapBounds {
+    /// Kill plane below the lowest ground, so maps with empty rows at the bottom still kill
+    pub fn kill_plane(&self) -> f32 {
+        self.floor - KILL_PLANE_DEPTH
+    }
+
+    /// Kill plane above the map for when gravity is flipped
+    pub fn ceiling_kill_plane(&self) -> f32 {
+        self.size.y + KILL_PLANE_DEPTH
+    }
+
+    /// Clamps a camera position so the view never leaves the map
+    pub fn clamp_camera(&self, position: Vec2) -> Vec2 {
+        self.clamp_zoomed_camera(position, 1.0)
+    }
+
+    /// Same as `clamp_camera` for a view scaled by the camera's projection
+    pub fn clamp_zoomed_camera(&self, position: Vec2, zoom: f32) -> Vec2 {
+        let half_view = Vec2::new(WIDTH, HEIGHT) * zoom / 2.0;
+        let max = (self.size - half_view).max(half_view);
+        position.clamp(half_view, max)
+    }
+
+    /// Largest zoom that still fits inside the map
+    pub fn max_zoom(&self) -> f32 {
+        (self.size.x / WIDTH).min(self.size.y / HEIGHT).max(1.0)
+    }
+}
+
+#[derive(Component)]
+pub struct Crate {
+    pub spawn: Vec2,
+}
+
+#[derive(Component, Default)]
+pub struct PressurePlate {
+    pub pressed: bool,
+}
+
+/// Blocks the way until any pressure plate in the level is held down
+#[derive(Component)]
+pub struct Gate;
+
+fn update_pressure_plates(
+    mut plates: Query<(&Transform, &mut PressurePlate, &mut Sprite)>,
+    weights: Query<(), Or<(With<Crate>, With<PlayerVelocity>)>>,
+    rapier_context: Res<RapierContext>,
+) {
+    for (transform, mut plate, mut sprite) in &mut plates {
+        let shape = Collider::cuboid(14.0, 4.0);
+        let shape_pos = transform.translation.truncate();
+        let filter = QueryFilter::default().exclude_sensors();
+
+        let mut pressed = false;
+        rapier_context.intersections_with_shape(shape_pos, 0.0, &shape, filter, |entity| {
+            pressed = weights.contains(entity);
+            !pressed
+        });
+
+        if pressed != plate.pressed {
+            plate.pressed = pressed;
+            sprite.color = if pressed {
+                Color::rgb(0.4, 0.8, 0.4)
+            } else {
+                Color::rgb(0.8, 0.4, 0.4)
+            };
+        }
+    }
+}
+
+fn update_gates(
+    mut commands: Commands,
+    plates: Query<&PressurePlate>,
+    mut gates: Query<(Entity, &mut Visibility), With<Gate>>,
+) {
+    let open = plates.iter().any(|plate| plate.pressed);
+    let target = if open {
+        Visibility::Hidden
+    } else {
+        Visibility::Inherited
+    };
+    for (entity, mut visibility) in &mut gates {
+        if *visibility == target {
+            continue;
+        }
+        *visibility = target;
+        if open {
+            commands.entity(entity).insert(ColliderDisabled);
+        } else {
+            commands.entity(entity).remove::<ColliderDisabled>();
+        }
+    }
+}
+
+fn reset_crates(
+    mut crates: Query<(&Crate, &mut Transform, &mut Velocity)>,
+    mut respawns: EventReader<PlayerRespawnedEvent>,
+) {
+    if respawns.iter().count() == 0 {
+        return;
+    }
+    for (crate_box, mut transform, mut velocity) in &mut crates {
+        transform.translation = crate_box.spawn.extend(transform.translation.z);
+        *velocity = Velocity::zero();
+    }
+}
+
 fn spawn_exit(commands: &mut Commands, translation: Vec2) {
     commands.spawn((
         Collider::cuboid(32.0, 32.0),
@@ -23,9 +158,9 @@ fn spawn_exit(commands: &mut Commands, translation: Vec2) {
 pub fn load_map(
     commands: &mut Commands,
     assets: &Res<AssetServer>,
-    progression: &StoryProgression,
+    progression: &mut StoryProgression,
 ) {
-    let map = &progression.levels[progression.current_map];
+    let map = progression.levels[progression.current_map].clone();
     //let file = File::open("assets/maps/test_room.map").unwrap();
     //let reader = BufReader::new(map);
 
@@ -39,6 +174,9 @@ pub fn load_map(
     let mut max_x = 0;
     let max_y = lines.len();
     let mut boxes_to_spawn = Vec::new();
+    let mut spawn = None;
+    let mut potion_spawn = None;
+    let mut portals: HashMap<char, Vec<Vec2>> = HashMap::default();
 
     for (y, line) in lines.iter().enumerate() {
         max_x = line.len();
@@ -67,6 +205,27 @@ pub fn load_map(
             if c == 'P' {
                 spawn_potion(commands, assets, Vec2::new(x as f32, y as f32));
             }
+            if c == 'S' {
+                spawn = Some(tile_center(x, y));
+            }
+            if c == 'A' {
+                potion_spawn = Some(tile_center(x, y));
+            }
+            if c.is_ascii_lowercase() {
+                portals.entry(c).or_default().push(tile_center(x, y));
+            }
+            if c == 'H' {
+                spawn_ladder(commands, tile_center(x, y));
+            }
+            if c == 'C' {
+                spawn_crate(commands, Vec2::new(x as f32, y as f32));
+            }
+            if c == 'B' {
+                spawn_pressure_plate(commands, Vec2::new(x as f32, y as f32));
+            }
+            if c == 'G' {
+                spawn_gate(commands, Vec2::new(x as f32, y as f32));
+            }
         }
         //Cleanup ongoing run
         if in_run {
@@ -74,6 +233,11 @@ pub fn load_map(
         }
     }
 
+    let floor = boxes_to_spawn
+        .iter()
+        .map(|(_, y, _)| *y)
+        .min()
+        .unwrap_or_default();
     for (x, y, width) in boxes_to_spawn {
         //FIXME can connect y values too
         spawn_hit_box(
@@ -83,8 +247,40 @@ pub fn load_map(
         );
     }
 
+    for (letter, ends) in portals {
+        if let [a, b] = ends[..] {
+            spawn_portal(commands, a, b);
+            spawn_portal(commands, b, a);
+        } else {
+            warn!(
+                "Portal {} needs exactly 2 ends, found {}",
+                letter,
+                ends.len()
+            );
+        }
+    }
+
     //Left wall
-    spawn_hit_box(commands, Vec2::new(1.0, 30.0), Vec2::new(-1.0, 0.0));
+    spawn_hit_box(
+        commands,
+        Vec2::new(1.0, max_y as f32 * 2.0),
+        Vec2::new(-1.0, 0.0),
+    );
+
+    let spawn = spawn.expect("Map has no player spawn");
+    progression.respawn_point = spawn.extend(CHARACTER_Z);
+    progression.potion_respawn_point = potion_spawn.unwrap_or(spawn).extend(CHARACTER_Z);
+
+    let bounds = MapBounds {
+        size: Vec2::new(max_x as f32, max_y as f32) * TILE_SIZE,
+        floor: floor as f32 * TILE_SIZE,
+    };
+    spawn_level_hazards(
+        commands,
+        &progression.level_settings[progression.current_map],
+        &bounds,
+    );
+    commands.insert_resource(bounds);
 
     commands.spawn((
         SpriteBundle {
@@ -126,6 +322,122 @@ fn spawn_potion(commands: &mut Commands, assets: &Res<AssetServer>, position: Ve
     ));
 }
 
+fn tile_center(x: usize, y: usize) -> Vec2 {
+    Vec2::new(x as f32, y as f32) * TILE_SIZE + Vec2::splat(TILE_SIZE / 2.0)
+}
+
+fn spawn_ladder(commands: &mut Commands, position: Vec2) {
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgba(0.65, 0.45, 0.25, 0.8),
+                custom_size: Some(Vec2::new(16.0, TILE_SIZE)),
+                ..default()
+            },
+            transform: Transform::from_translation(position.extend(BACKGROUND_Z + 1.0)),
+            ..default()
+        },
+        Collider::cuboid(8.0, TILE_SIZE / 2.0),
+        Sensor,
+        Ladder,
+        MapEntity,
+        Name::new("Ladder"),
+    ));
+}
+
+fn spawn_portal(commands: &mut Commands, position: Vec2, exit: Vec2) {
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgba(0.6, 0.3, 0.9, 0.8),
+                custom_size: Some(Vec2::new(20.0, 30.0)),
+                ..default()
+            },
+            transform: Transform::from_translation(position.extend(ENEMY_Z)),
+            ..default()
+        },
+        Collider::cuboid(10.0, 15.0),
+        Sensor,
+        Portal { exit },
+        MapEntity,
+        Name::new("Portal"),
+    ));
+}
+
+fn spawn_crate(commands: &mut Commands, position: Vec2) {
+    let spawn = position * TILE_SIZE + Vec2::splat(TILE_SIZE / 2.0);
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgb(0.55, 0.35, 0.2),
+                custom_size: Some(Vec2::splat(CRATE_SIZE)),
+                ..default()
+            },
+            transform: Transform::from_translation(spawn.extend(ENEMY_Z)),
+            ..default()
+        },
+        RigidBody::Dynamic,
+        Collider::cuboid(CRATE_SIZE / 2.0, CRATE_SIZE / 2.0),
+        ColliderMassProperties::Mass(CRATE_MASS),
+        //World gravity is tuned for floaty effects, crates should feel heavy
+        GravityScale(5.0),
+        Friction::coefficient(0.8),
+        LockedAxes::ROTATION_LOCKED,
+        Velocity::zero(),
+        ExternalImpulse::default(),
+        PhysicsInterpolation::new(spawn.extend(ENEMY_Z)),
+        Crate { spawn },
+        Rewindable,
+        MapEntity,
+        Name::new("Crate"),
+    ));
+}
+
+fn spawn_pressure_plate(commands: &mut Commands, position: Vec2) {
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgb(0.8, 0.4, 0.4),
+                custom_size: Some(Vec2::new(28.0, 4.0)),
+                ..default()
+            },
+            transform: Transform::from_xyz(
+                position.x * 32.0 + 16.0,
+                position.y * 32.0 + 2.0,
+                900.0,
+            ),
+            ..default()
+        },
+        Collider::cuboid(14.0, 2.0),
+        Sensor,
+        PressurePlate::default(),
+        MapEntity,
+        Name::new("PressurePlate"),
+    ));
+}
+
+fn spawn_gate(commands: &mut Commands, position: Vec2) {
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgb(0.5, 0.5, 0.6),
+                custom_size: Some(Vec2::splat(TILE_SIZE)),
+                ..default()
+            },
+            transform: Transform::from_xyz(
+                position.x * 32.0 + 16.0,
+                position.y * 32.0 + 16.0,
+                900.0,
+            ),
+            ..default()
+        },
+        Collider::cuboid(16.0, 16.0),
+        Gate,
+        MapEntity,
+        Name::new("Gate"),
+    ));
+}
+
 fn spawn_hit_box(commands: &mut Commands, block_size: Vec2, bottom_left_position: Vec2) {
     let half_size = block_size * Vec2::splat(16.0);
     commands
@@ -138,3 +450,46 @@ fn spawn_hit_box(commands: &mut Commands, block_size: Vec2, bottom_left_position
         .insert(MapEntity)
         .insert(Name::new("Hitbox"));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounds(size: Vec2) -> MapBounds {
+        MapBounds { size, floor: 0.0 }
+    }
+
+    #[test]
+    fn camera_stays_inside_the_map() {
+        let bounds = bounds(Vec2::new(2000.0, 1000.0));
+        let half_view = Vec2::new(WIDTH, HEIGHT) / 2.0;
+        assert_eq!(bounds.clamp_camera(Vec2::ZERO), half_view);
+        assert_eq!(
+            bounds.clamp_camera(Vec2::splat(5000.0)),
+            bounds.size - half_view
+        );
+        assert_eq!(
+            bounds.clamp_camera(Vec2::new(900.0, 500.0)),
+            Vec2::new(900.0, 500.0)
+        );
+    }
+
+    #[test]
+    fn zoomed_camera_keeps_the_wider_view_inside() {
+        let bounds = bounds(Vec2::new(2000.0, 1000.0));
+        let half_view = Vec2::new(WIDTH, HEIGHT);
+        assert_eq!(bounds.clamp_zoomed_camera(Vec2::ZERO, 2.0), half_view);
+        assert_eq!(
+            bounds.clamp_zoomed_camera(Vec2::splat(5000.0), 2.0),
+            bounds.size - half_view
+        );
+    }
+
+    #[test]
+    fn small_maps_pin_the_camera_to_the_bottom_left() {
+        let bounds = bounds(Vec2::new(WIDTH / 2.0, HEIGHT / 2.0));
+        let half_view = Vec2::new(WIDTH, HEIGHT) / 2.0;
+        assert_eq!(bounds.clamp_camera(Vec2::splat(100.0)), half_view);
+        assert_eq!(bounds.max_zoom(), 1.0);
+    }
+}
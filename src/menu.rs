@@ -1,3 +1,5 @@
+use std::marker::PhantomData;
+
 use crate::prelude::*;
 
 pub struct MenuPlugin;
@@ -7,25 +9,149 @@ impl Plugin for MenuPlugin {
         app.add_system(spawn_menu_ui.in_schedule(OnEnter(GameState::Menu)))
             .add_system(despawn_with::<MenuElement>.in_schedule(OnExit(GameState::Menu)))
             .add_system(spawn_win_ui.in_schedule(OnEnter(GameState::Win)))
-            .add_systems((update_menu_ui, exit_menu).in_set(OnUpdate(GameState::Menu)));
+            .add_systems(
+                (
+                    update_menu_ui,
+                    toggle_setting::<GhostSettings>,
+                    toggle_setting::<RewindSettings>,
+                    toggle_setting::<CoOpSettings>,
+                    exit_menu,
+                )
+                    .in_set(OnUpdate(GameState::Menu)),
+            )
+            .add_system(pause_game.in_set(OnUpdate(GameState::Platforming)))
+            .add_system(unpause_game.in_set(OnUpdate(GameState::Paused)))
+            .add_system(spawn_pause_ui.in_schedule(OnEnter(GameState::Paused)))
+            .add_system(despawn_with::<PauseElement>.in_schedule(OnExit(GameState::Paused)));
     }
 }
 
 #[derive(Component)]
 struct MenuElement;
 
+#[derive(Component)]
+struct PauseElement;
+
+#[derive(Component)]
+struct PlayButton;
+
+/// An on/off setting shown as a button on the title screen
+trait MenuToggle: Resource {
+    const LABEL: &'static str;
+
+    fn enabled(&self) -> bool;
+    fn set_enabled(&mut self, enabled: bool);
+}
+
+impl MenuToggle for GhostSettings {
+    const LABEL: &'static str = "Ghost";
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+}
+
+impl MenuToggle for RewindSettings {
+    const LABEL: &'static str = "Rewind Assist";
+
+    fn enabled(&self) -> bool {
+        self.assist
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.assist = enabled;
+    }
+}
+
+impl MenuToggle for CoOpSettings {
+    const LABEL: &'static str = "Co-op";
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+}
+
+#[derive(Component)]
+struct ToggleButton<T: MenuToggle>(PhantomData<T>);
+
+fn pause_game(
+    actions: Res<Input<Action>>,
+    mut rapier: ResMut<RapierConfiguration>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if actions.just_pressed(Action::Pause) {
+        rapier.physics_pipeline_active = false;
+        next_state.set(GameState::Paused);
+    }
+}
+
+fn unpause_game(
+    actions: Res<Input<Action>>,
+    rewind: Res<RewindState>,
+    mut rapier: ResMut<RapierConfiguration>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if actions.just_pressed(Action::Pause) {
+        rapier.physics_pipeline_active = !rewind.rewinding;
+        next_state.set(GameState::Platforming);
+    }
+}
+
+fn spawn_pause_ui(mut commands: Commands, assets: Res<AssetServer>) {
+    //FIXME: Global font setting
+    let font = assets.load("fonts/pointfree.ttf");
+
+    let parent = (
+        NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+            background_color: Color::rgba(0.1, 0.1, 0.1, 0.6).into(),
+            ..default()
+        },
+        PauseElement,
+        Name::new("Pause UI"),
+    );
+
+    let pause_text = TextBundle::from_section(
+        "Paused",
+        TextStyle {
+            font,
+            font_size: 48.0,
+            color: Color::WHITE,
+        },
+    );
+    commands.spawn(parent).with_children(|commands| {
+        commands.spawn(pause_text);
+    });
+}
+
 #[derive(Component)]
 struct MenuFade;
 
 fn spawn_win_ui(
     mut commands: Commands,
     assets: Res<AssetServer>,
-    mut camera: Query<&mut Transform, With<MainCamera>>,
+    mut camera: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
     timer: Query<&TimerUI>,
 ) {
-    let mut camera = camera.single_mut();
+    let (mut camera, mut projection) = camera.single_mut();
     let timer = timer.single();
     camera.translation.x = WIDTH / 2.0;
+    camera.translation.y = HEIGHT / 2.0;
+    projection.scale = 1.0;
 
     commands.spawn((
         SpriteBundle {
@@ -75,7 +201,13 @@ fn spawn_win_ui(
     });
 }
 
-fn spawn_menu_ui(mut commands: Commands, assets: Res<AssetServer>) {
+fn spawn_menu_ui(
+    mut commands: Commands,
+    assets: Res<AssetServer>,
+    settings: Res<GhostSettings>,
+    rewind_settings: Res<RewindSettings>,
+    co_op: Res<CoOpSettings>,
+) {
     commands.spawn((
         SpriteBundle {
             transform: Transform::from_xyz(640.0 / 2.0, 480.0 / 2.0, 100.0),
@@ -108,13 +240,14 @@ fn spawn_menu_ui(mut commands: Commands, assets: Res<AssetServer>) {
             ..default()
         },
         MenuElement,
+        PlayButton,
         Name::new("Button"),
     );
 
     let menu_text = (TextBundle::from_section(
         "Play Game",
         TextStyle {
-            font,
+            font: font.clone(),
             font_size: 48.0,
             color: Color::BLACK,
         },
@@ -123,18 +256,96 @@ fn spawn_menu_ui(mut commands: Commands, assets: Res<AssetServer>) {
     commands.spawn(parent).with_children(|commands| {
         commands.spawn(menu_text);
     });
+
+    spawn_toggle_button(&mut commands, font.clone(), 37.5, &*settings);
+    spawn_toggle_button(&mut commands, font.clone(), 5.0, &*rewind_settings);
+    spawn_toggle_button(&mut commands, font, 70.0, &*co_op);
 }
 
-fn update_menu_ui(mut commands: Commands, fade: Query<&Fadeout>, button: Query<&Interaction>) {
+/// `right` is the button's offset from the right edge in percent of the screen
+fn spawn_toggle_button<T: MenuToggle>(
+    commands: &mut Commands,
+    font: Handle<Font>,
+    right: f32,
+    settings: &T,
+) {
+    let parent = (
+        ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Percent(25.0), Val::Percent(8.0)),
+                align_self: AlignSelf::Center,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                position_type: PositionType::Absolute,
+                position: UiRect::new(
+                    Val::Undefined,
+                    Val::Percent(right),
+                    Val::Percent(87.0),
+                    Val::Undefined,
+                ),
+                ..default()
+            },
+            background_color: Color::rgb(154.0 / 255.0, 151.0 / 255.0, 185.0 / 255.0).into(),
+            ..default()
+        },
+        MenuElement,
+        ToggleButton::<T>(PhantomData),
+        Name::new(format!("{} Button", T::LABEL)),
+    );
+
+    let text = TextBundle::from_section(
+        toggle_label(settings),
+        TextStyle {
+            font,
+            font_size: 24.0,
+            color: Color::BLACK,
+        },
+    );
+    commands.spawn(parent).with_children(|commands| {
+        commands.spawn(text);
+    });
+}
+
+fn toggle_label<T: MenuToggle>(settings: &T) -> String {
+    if settings.enabled() {
+        format!("{}: On", T::LABEL)
+    } else {
+        format!("{}: Off", T::LABEL)
+    }
+}
+
+fn toggle_setting<T: MenuToggle>(
+    button: Query<(&Interaction, &Children), (Changed<Interaction>, With<ToggleButton<T>>)>,
+    mut text: Query<&mut Text>,
+    mut settings: ResMut<T>,
+) {
+    for (interaction, children) in &button {
+        if interaction == &Interaction::Clicked {
+            let enabled = !settings.enabled();
+            settings.set_enabled(enabled);
+            for child in children {
+                if let Ok(mut text) = text.get_mut(*child) {
+                    text.sections[0].value = toggle_label(&*settings);
+                }
+            }
+        }
+    }
+}
+
+fn update_menu_ui(
+    mut commands: Commands,
+    fade: Query<&Fadeout>,
+    button: Query<&Interaction, With<PlayButton>>,
+    actions: Res<Input<Action>>,
+) {
     //FIXME make this a run condition
     if fade.iter().count() != 0 {
         return;
     }
-    for button in &button {
-        if button == &Interaction::Clicked {
-            let entity = spawn_fadeout(&mut commands, 0.4, 0.2, 0.2);
-            commands.entity(entity).insert(MenuFade);
-        }
+    let clicked = button.iter().any(|button| button == &Interaction::Clicked);
+    if clicked || actions.just_pressed(Action::Interact) {
+        let entity = spawn_fadeout(&mut commands, 0.4, 0.2, 0.2);
+        commands.entity(entity).insert(MenuFade);
     }
 }
 
@@ -1,3 +1,7 @@
+use std::time::Duration;
+
+use bevy::{ecs::query::ReadOnlyWorldQuery, sprite::Anchor};
+use bevy_easings::{custom_ease_system, CustomComponentEase, EaseFunction, EasingType, Lerp};
 use serde::{Deserialize, Serialize};
 
 use crate::prelude::*;
@@ -6,31 +10,92 @@ pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
+        app.insert_resource(
+            ron::from_str::<DeathSettings>(include_str!("../assets/animations/death.ron")).unwrap(),
+        )
+        .add_systems(
             (
                 player_respawn,
                 player_exit_level,
+                player_size,
                 player_gravity,
                 player_jump,
+                player_climb,
+                player_push,
+                player_crouch,
                 player_control,
+                player_dash,
+                player_grapple,
                 player_update,
-                player_death,
-                player_pickups,
             )
                 .chain()
-                .in_set(OnUpdate(GameState::Platforming)),
+                .in_set(PlayerMovementSet)
+                .before(PhysicsSet::SyncBackend)
+                .distributive_run_if(simulation_running)
+                .in_schedule(CoreSchedule::FixedUpdate),
+        )
+        .add_systems(
+            (player_death, player_pickups, player_portals)
+                .chain()
+                .in_set(PlayerCheckSet)
+                .after(PhysicsSet::Writeback)
+                .distributive_run_if(simulation_running)
+                .in_schedule(CoreSchedule::FixedUpdate),
+        )
+        .add_system(
+            player_lean
+                .after(PlayerMovementSet)
+                .run_if(simulation_running)
+                .in_schedule(CoreSchedule::FixedUpdate),
         )
         .add_system(player_particles)
+        .add_system(player_death_effects)
         .add_system(apply_player_effect)
-        .add_system(player_animation);
+        .add_system(player_animation)
+        .add_system(update_grapple_ropes)
+        .add_system(player_landing_squash)
+        .add_system(custom_ease_system::<Squash>.after(player_landing_squash))
+        .add_system(player_squash_stretch.after(custom_ease_system::<Squash>));
     }
 }
 
+/// Player movement systems, these run in the fixed timestep before physics
+#[derive(SystemSet, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct PlayerMovementSet;
+
+/// Death, pickup and teleport checks, these run in the fixed timestep after physics
+#[derive(SystemSet, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct PlayerCheckSet;
+
+/// 0 for player one, picks the player's bindings and replay slot
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PlayerIndex(pub usize);
+
 #[derive(Component)]
 pub struct PlayerVelocity {
     pub velocity: Vec2,
+    /// Last wall touched, kept while airborne so wall jumps work during the grace period
     pub on_wall: OnWall,
-    pub last_on_wall: usize,
+    pub time_since_wall: f32,
+    pub climbing: bool,
+    pub coyote_timer: f32,
+    pub jump_buffer: f32,
+    pub air_jumps_used: u32,
+    /// Time left in the current dash
+    pub dash_timer: f32,
+    pub dash_cooldown: f32,
+    pub dash_direction: Vec2,
+    pub wall_sliding: bool,
+    pub gliding: bool,
+    pub crouching: bool,
+    /// Time left in the current slide
+    pub slide_timer: f32,
+    /// Point the rope is attached to
+    pub grapple: Option<Vec2>,
+    pub grapple_length: f32,
+    pub grapple_cooldown: f32,
+    /// Set while speed from a swing is above the normal max, which bleeds off instead of being cut
+    pub swing_momentum: bool,
 }
 
 #[derive(PartialEq, Eq, Debug)]
@@ -50,8 +115,107 @@ pub struct PlayerStats {
     pub jump_strength: f32,
     pub can_wall_jump: bool,
     pub wall_jump_strength: f32,
+    pub wall_jump_grace: f32,
+    pub push_strength: f32,
+    pub climb_speed: f32,
+    pub coyote_time: f32,
+    pub jump_buffer: f32,
+    /// Upward velocity is multiplied by this when jump is released, 1.0 keeps the full float
+    pub jump_cut_multiplier: f32,
+    /// Speed during a dash, 0.0 disables dashing
+    pub dash_speed: f32,
+    pub dash_time: f32,
+    pub dash_cooldown: f32,
+    /// Extra jumps allowed before touching the ground again
+    pub air_jumps: u32,
+    /// Slows falling while touching a wall, 0.0 disables wall sliding
+    pub wall_slide_friction: f32,
+    /// Max fall speed while holding jump, 0.0 disables gliding
+    pub glide_speed: f32,
+    /// Horizontal slow down while sliding
+    pub slide_friction: f32,
+    /// Longest a slide lasts, 0.0 disables sliding
+    pub slide_time: f32,
+    /// Longest rope the grapple can fire, 0.0 disables grappling
+    pub grapple_range: f32,
+    pub grapple_cooldown: f32,
+    /// Seconds of history the player can rewind through, 0.0 disables rewinding
+    pub rewind_time: f32,
+    /// The player falls upwards and walks on ceilings
+    pub gravity_flipped: bool,
+    /// Scales the sprite, collider and particle offsets
+    pub size: f32,
+}
+
+impl PlayerStats {
+    /// 1.0 when gravity pulls down, -1.0 when it is flipped
+    pub fn up(&self) -> f32 {
+        if self.gravity_flipped {
+            -1.0
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Squash from landing, eased back to `Vec2::ONE` by bevy_easings
+#[derive(Component, Clone, Copy)]
+pub struct Squash(pub Vec2);
+
+impl Default for Squash {
+    fn default() -> Self {
+        Self(Vec2::ONE)
+    }
 }
 
+impl Lerp for Squash {
+    type Scalar = f32;
+
+    fn lerp(&self, other: &Self, scalar: &f32) -> Self {
+        Self(self.0.lerp(other.0, *scalar))
+    }
+}
+
+/// Tilt towards the player's horizontal acceleration
+#[derive(Component, Default)]
+pub struct Lean {
+    pub angle: f32,
+    /// Angle the sprite eases towards, set each simulation tick
+    target: f32,
+    previous_velocity: f32,
+}
+
+/// Half extents of the player's collider while standing
+pub const PLAYER_HALF_SIZE: Vec2 = Vec2::new(17.0 / 2.0, 28.0 / 2.0);
+/// Fraction of the standing height kept while crouched
+pub const CROUCH_HEIGHT: f32 = 0.5;
+/// Fraction of max velocity needed to start a slide, also the crouch walking speed
+const SLIDE_SPEED: f32 = 0.5;
+
+pub fn player_collider(crouching: bool) -> Collider {
+    if crouching {
+        Collider::cuboid(PLAYER_HALF_SIZE.x, PLAYER_HALF_SIZE.y * CROUCH_HEIGHT)
+    } else {
+        Collider::cuboid(PLAYER_HALF_SIZE.x, PLAYER_HALF_SIZE.y)
+    }
+}
+
+/// Players pass through each other, everything else still collides with them
+pub const PLAYER_GROUP: Group = Group::GROUP_2;
+
+pub fn player_collision_groups() -> CollisionGroups {
+    CollisionGroups::new(PLAYER_GROUP, Group::ALL)
+}
+
+/// Groups for the player's own movement and shape checks
+pub fn player_filter_groups() -> CollisionGroups {
+    CollisionGroups::new(Group::ALL, !PLAYER_GROUP)
+}
+
+/// Drawn between a player and their grapple point
+#[derive(Component)]
+pub struct GrappleRope(pub Entity);
+
 #[derive(Component)]
 pub struct PlayerFeetParticles;
 
@@ -59,90 +223,222 @@ pub struct PlayerFeetParticles;
 pub struct PlayerHeadParticles;
 
 #[derive(Component)]
-pub struct DeathFade;
+pub struct PortalCooldown(pub Timer);
 
+/// Counts down with the simulation until the player respawns
 #[derive(Component)]
-pub struct ExitFade;
+pub struct RespawnTimer(pub Timer);
+
+/// Counts down with the simulation until the next level loads
+#[derive(Component)]
+pub struct ExitTimer(pub Timer);
+
+/// Waiting inside the door for the other players
+#[derive(Component)]
+pub struct ReachedExit;
+
+/// Timings for the death sequence, loaded from `assets/animations/death.ron`
+#[derive(Resource, Serialize, Deserialize, Clone, Copy)]
+pub struct DeathSettings {
+    /// Seconds the death animation plays before the screen fades, input is frozen throughout
+    pub animation_time: f32,
+    pub fade_in: f32,
+    pub fade_hold: f32,
+    pub fade_out: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DeathCause {
+    /// Left through the bottom of the map, or the top when gravity is flipped
+    Fell,
+    /// Caught by a rising hazard
+    Hazard,
+    /// Left behind by the auto scrolling camera
+    OffScreen,
+}
+
+impl DeathSettings {
+    /// The player respawns once the screen has faded in
+    pub fn respawn_time(&self) -> f32 {
+        self.animation_time + self.fade_in
+    }
+}
+pub const EXIT_FADE_TIME: f32 = 1.0;
 
 fn player_death(
     mut commands: Commands,
-    player: Query<&Transform, With<PlayerStats>>,
-    fade: Query<&DeathFade>,
+    player: Query<
+        (Entity, &Transform, &PlayerStats),
+        (Without<RespawnTimer>, Without<ReachedExit>),
+    >,
+    bounds: Res<MapBounds>,
+    settings: Res<DeathSettings>,
+    mut writer: EventWriter<PlayerDiedEvent>,
 ) {
-    if fade.iter().count() > 0 {
-        return;
-    }
-    let player = player.single();
-    if player.translation.y < -96.0 {
-        let fade = spawn_fadeout(&mut commands, 1.0, 0.4, 1.0);
-        commands.entity(fade).insert(DeathFade);
+    for (entity, transform, stats) in &player {
+        //Only falling off the side gravity pulls towards kills
+        let y = transform.translation.y;
+        let fell = if stats.up() > 0.0 {
+            y < bounds.kill_plane()
+        } else {
+            y > bounds.ceiling_kill_plane()
+        };
+        if fell {
+            kill_player(
+                &mut commands,
+                &mut writer,
+                entity,
+                transform.translation.truncate(),
+                DeathCause::Fell,
+                &settings,
+            );
+        }
     }
 }
 
+pub fn kill_player(
+    commands: &mut Commands,
+    writer: &mut EventWriter<PlayerDiedEvent>,
+    player: Entity,
+    position: Vec2,
+    cause: DeathCause,
+    settings: &DeathSettings,
+) {
+    info!("Player died: {:?}", cause);
+    writer.send(PlayerDiedEvent {
+        player,
+        cause,
+        position,
+    });
+    commands
+        .entity(player)
+        .insert(RespawnTimer(Timer::from_seconds(
+            settings.respawn_time(),
+            TimerMode::Once,
+        )));
+}
+
 #[allow(clippy::too_many_arguments)]
 fn player_exit_level(
     mut commands: Commands,
     assets: Res<AssetServer>,
-    mut player: Query<(&mut PlayerVelocity, &mut Transform), With<PlayerStats>>,
+    mut player: Query<(
+        Entity,
+        &mut PlayerVelocity,
+        &mut Transform,
+        &mut Visibility,
+        &mut ExitTimer,
+    )>,
     //TODO despawn on event with util system
     map_entities: Query<Entity, With<MapEntity>>,
     mut disable_effects: EventWriter<DisableEffectsEvent>,
     mut progression: ResMut<StoryProgression>,
-    fade: Query<&Fadeout, With<ExitFade>>,
     mut next_state: ResMut<NextState<GameState>>,
+    time: Res<FixedTime>,
 ) {
-    for fade in &fade {
-        info!("fade");
-        let (mut velocity, mut player) = player.single_mut();
+    let mut finished = false;
+    for (_, mut velocity, _, _, mut timer) in &mut player {
         velocity.velocity.x = 0.0;
-        if fade.fade_in_just_finished {
-            for map_ent in &map_entities {
-                commands.entity(map_ent).despawn_recursive();
-            }
-            progression.respawn_alt = false;
-            progression.current_map += 1;
-            disable_effects.send(DisableEffectsEvent);
-            if progression.current_map >= progression.levels.len() {
-                info!("Win");
-                next_state.set(GameState::Win);
-            } else {
-                load_map(&mut commands, &assets, &progression);
-                player.translation = progression.respawn_point;
-                next_state.set(GameState::Cutscene);
-            }
-            return;
+        timer.0.tick(time.period);
+        finished |= timer.0.finished();
+    }
+    //Every player's timer starts on the same tick so the level only changes once
+    if !finished {
+        return;
+    }
+
+    for map_ent in &map_entities {
+        commands.entity(map_ent).despawn_recursive();
+    }
+    progression.respawn_alt = false;
+    progression.current_map += 1;
+    disable_effects.send(DisableEffectsEvent);
+    let won = progression.current_map >= progression.levels.len();
+    if won {
+        info!("Win");
+        next_state.set(GameState::Win);
+    } else {
+        load_map(&mut commands, &assets, &mut progression);
+        next_state.set(GameState::Cutscene);
+    }
+
+    for (entity, mut velocity, mut player, mut visibility, _) in &mut player {
+        commands.entity(entity).remove::<(ExitTimer, ReachedExit)>();
+        *visibility = Visibility::Inherited;
+        if !won {
+            player.translation = progression.respawn_point;
+            velocity.velocity = Vec2::ZERO;
+            velocity.dash_timer = 0.0;
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn player_respawn(
-    mut player: Query<(&mut PlayerVelocity, &mut Transform), With<PlayerStats>>,
+    mut commands: Commands,
+    mut player: Query<(
+        Entity,
+        &mut PlayerVelocity,
+        &mut Transform,
+        &mut Collider,
+        &mut Visibility,
+        &mut TickActions,
+        &mut RespawnTimer,
+    )>,
     progression: Res<StoryProgression>,
-    fade: Query<&Fadeout, With<DeathFade>>,
+    settings: Res<DeathSettings>,
+    mut writer: EventWriter<PlayerRespawnedEvent>,
+    time: Res<FixedTime>,
 ) {
-    if let Ok(fade) = fade.get_single() {
-        if fade.fade_in_just_finished {
-            let (mut velocity, mut player) = player.single_mut();
+    for (entity, mut velocity, mut player, mut collider, mut visibility, mut actions, mut timer) in
+        &mut player
+    {
+        //Input is frozen until the player respawns
+        actions.set_mask(0);
+
+        //The fade is only visual, the respawn is timed by the simulation
+        let before = timer.0.elapsed_secs();
+        timer.0.tick(time.period);
+        if before <= settings.animation_time && timer.0.elapsed_secs() > settings.animation_time {
+            spawn_fadeout(
+                &mut commands,
+                settings.fade_in,
+                settings.fade_hold,
+                settings.fade_out,
+            );
+        }
+
+        if timer.0.finished() {
             if progression.respawn_alt {
-                player.translation =
-                    progression.potion_spawns[progression.current_map].extend(CHARACTER_Z);
+                player.translation = progression.potion_respawn_point;
             } else {
                 player.translation = progression.respawn_point;
             }
             velocity.velocity = Vec2::ZERO;
+            velocity.dash_timer = 0.0;
+            velocity.grapple = None;
+            if velocity.crouching {
+                velocity.crouching = false;
+                velocity.slide_timer = 0.0;
+                *collider = player_collider(false);
+            }
+            *visibility = Visibility::Inherited;
+            commands.entity(entity).remove::<RespawnTimer>();
+            writer.send(PlayerRespawnedEvent);
         }
     }
 }
 
 fn apply_player_effect(
     progression: Res<StoryProgression>,
-    fadeout: Query<&Fadeout, With<PotionFade>>,
-    mut player: Query<&mut PlayerStats, With<PlayerVelocity>>,
+    fadeout: Query<(&Fadeout, &PotionFade)>,
+    mut player: Query<(&mut PotionEffects, &mut PlayerStats), With<PlayerVelocity>>,
 ) {
-    if let Ok(fadeout) = fadeout.get_single() {
+    for (fadeout, potion) in &fadeout {
         if fadeout.fade_in_just_finished {
-            for mut stats in &mut player {
-                *stats = progression.potion_effects[progression.current_map];
+            for (mut effects, mut stats) in &mut player {
+                effects.add(progression.potion_effects[potion.0].clone());
+                *stats = effects.stats();
             }
         }
     }
@@ -155,19 +451,35 @@ fn player_pickups(
     exits: Query<&Name, (With<Sensor>, With<Door>, Without<Potion>)>,
     mut progression: ResMut<StoryProgression>,
     rapier_context: Res<RapierContext>,
-    player: Query<&Transform, With<PlayerVelocity>>,
+    player: Query<
+        (Entity, &Transform, &PlayerStats),
+        (
+            With<PlayerVelocity>,
+            Without<ExitTimer>,
+            Without<ReachedExit>,
+        ),
+    >,
+    players: Query<(Entity, Option<&ReachedExit>), With<PlayerVelocity>>,
     //TODO potions hold refrence to effect?
-    //mut event: EventWriter<PotionPickupEvent>,
+    mut potion_writer: EventWriter<PotionDrunkEvent>,
+    mut exit_writer: EventWriter<LevelExitedEvent>,
     mut next_state: ResMut<NextState<GameState>>,
 ) {
-    for transform in &player {
-        let shape = Collider::cuboid(15.0, 15.0);
+    let mut arrived = Vec::new();
+    let mut doors = Vec::new();
+    let mut drunk = Vec::new();
+    for (player, transform, stats) in &player {
+        let shape = Collider::cuboid(15.0 * stats.size, 15.0 * stats.size);
         let shape_pos = transform.translation.truncate();
         let filter = QueryFilter::default();
 
         rapier_context.intersections_with_shape(shape_pos, 0.0, &shape, filter, |entity| {
-            if let Ok(_sensors) = sensors.get(entity) {
-                //event.send(PotionPickupEvent(progression.current_map));
+            //Both players can touch the same potion on one tick
+            if sensors.contains(entity) && !drunk.contains(&entity) {
+                drunk.push(entity);
+                potion_writer.send(PotionDrunkEvent {
+                    level: progression.current_map,
+                });
                 let fade = spawn_fadeout(&mut commands, 0.4, 0.1, 0.2);
                 commands
                     .entity(fade)
@@ -179,70 +491,604 @@ fn player_pickups(
             }
             if let Ok(door) = exits.get(entity) {
                 info!("Hit Door {:?} {:?}", entity, door);
-                let fadeout = spawn_fadeout(&mut commands, 1.0, 0.4, 1.0);
-                commands.entity(fadeout).insert(ExitFade);
-                commands.entity(entity).despawn_recursive();
+                arrived.push(player);
+                doors.push(entity);
             }
             //XXX what does this do...
             true
         });
     }
+
+    if arrived.is_empty() {
+        return;
+    }
+    //The level ends once every player is through the door
+    if players
+        .iter()
+        .all(|(entity, waiting)| waiting.is_some() || arrived.contains(&entity))
+    {
+        exit_writer.send(LevelExitedEvent);
+        spawn_fadeout(&mut commands, EXIT_FADE_TIME, 0.4, 1.0);
+        for (entity, _) in &players {
+            commands
+                .entity(entity)
+                .insert(ExitTimer(Timer::from_seconds(
+                    EXIT_FADE_TIME,
+                    TimerMode::Once,
+                )));
+        }
+        for door in doors {
+            commands.entity(door).despawn_recursive();
+        }
+    } else {
+        for player in arrived {
+            commands
+                .entity(player)
+                .insert((ReachedExit, Visibility::Hidden));
+        }
+    }
+}
+
+fn player_portals(
+    mut commands: Commands,
+    particle_atlas: Res<ParticleAtlas>,
+    portals: Query<&Portal, With<Sensor>>,
+    rapier_context: Res<RapierContext>,
+    mut player: Query<(
+        Entity,
+        &mut Transform,
+        &mut PlayerVelocity,
+        &PlayerStats,
+        Option<&mut PortalCooldown>,
+    )>,
+    time: Res<FixedTime>,
+) {
+    for (entity, mut transform, mut velocity, stats, cooldown) in &mut player {
+        let shape = Collider::cuboid(8.0 * stats.size, 14.0 * stats.size);
+        let shape_pos = transform.translation.truncate();
+        let filter = QueryFilter::default();
+
+        let mut exit = None;
+        rapier_context.intersections_with_shape(shape_pos, 0.0, &shape, filter, |entity| {
+            exit = portals.get(entity).ok().map(|portal| portal.exit);
+            exit.is_none()
+        });
+
+        if let Some(mut cooldown) = cooldown {
+            cooldown.0.tick(time.period);
+            //Stay on cooldown until the player steps off the exit portal
+            if cooldown.0.finished() && exit.is_none() {
+                commands.entity(entity).remove::<PortalCooldown>();
+            }
+            continue;
+        }
+
+        if let Some(exit) = exit {
+            for position in [transform.translation.truncate(), exit] {
+                spawn_portal_particles(&mut commands, particle_atlas.0.clone(), position);
+            }
+
+            //Velocity is kept so momentum carries through the portal
+            transform.translation = exit.extend(CHARACTER_Z);
+            velocity.grapple = None;
+            commands
+                .entity(entity)
+                .insert(PortalCooldown(Timer::from_seconds(0.5, TimerMode::Once)));
+        }
+    }
+}
+
+fn spawn_portal_particles(
+    commands: &mut Commands,
+    particle_atlas: Handle<TextureAtlas>,
+    position: Vec2,
+) {
+    spawn_new_rect_emitter(
+        commands,
+        ParticleDesc {
+            particle: Particle::new(0.5),
+            sprite: SpriteSheetBundle {
+                sprite: TextureAtlasSprite {
+                    color: Color::rgb(0.7, 0.4, 1.0),
+                    custom_size: Some(Vec2::splat(8.0)),
+                    ..default()
+                },
+                texture_atlas: particle_atlas,
+                ..default()
+            },
+            falling: None,
+            radial: Some(RadialParticle {
+                speed: 6.0,
+                direction: Vec2::ZERO,
+            }),
+            rotating: Some(RotatingParticle { speed: 6.0 }),
+            fading: Some(FadingParticle {}),
+        },
+        position,
+        Vec2::new(20.0, 30.0),
+        Some(0.1),
+        1,
+        Some(0.01),
+    );
+}
+
+//Each player's particle emitters are its children
+fn child_emitter<'a, F: ReadOnlyWorldQuery>(
+    children: &Children,
+    emitters: &'a mut Query<&mut RectParticleEmitter, F>,
+) -> Option<Mut<'a, RectParticleEmitter>> {
+    let child = children.iter().find(|child| emitters.contains(**child))?;
+    emitters.get_mut(*child).ok()
 }
 
 fn player_particles(
-    player: Query<(&PlayerVelocity, &KinematicCharacterControllerOutput)>,
+    player: Query<(Entity, &Children, &PlayerVelocity, &PlayerState)>,
     mut player_particles: Query<&mut RectParticleEmitter, With<PlayerFeetParticles>>,
+    mut player_head_particles: Query<
+        &mut RectParticleEmitter,
+        (With<PlayerHeadParticles>, Without<PlayerFeetParticles>),
+    >,
+    mut landings: EventReader<LandedEvent>,
+) {
+    let landings: Vec<&LandedEvent> = landings.iter().collect();
+    for (entity, children, player, state) in &player {
+        let impact = landings
+            .iter()
+            .filter(|landing| landing.player == entity)
+            .map(|landing| landing.impact_speed)
+            .fold(0.0, f32::max);
+        if player.gliding {
+            if let Some(mut particles) = child_emitter(children, &mut player_head_particles) {
+                particles.force_spawn = 1;
+            }
+        }
+        if let Some(mut particles) = child_emitter(children, &mut player_particles) {
+            if *state == PlayerState::Grounded {
+                particles.force_spawn = player.velocity.x.abs() as usize / 180;
+            }
+            if player.wall_sliding {
+                particles.force_spawn = 1;
+            }
+            if *state == PlayerState::Sliding {
+                particles.force_spawn = 2;
+            }
+            //Bigger puff of dust the harder the landing
+            particles.force_spawn += impact as usize / 60;
+        }
+    }
+}
+
+//Hides the player and leaves a spinning copy behind while the death fade plays
+fn player_death_effects(
+    mut commands: Commands,
+    particle_atlas: Res<ParticleAtlas>,
+    mut player: Query<
+        (
+            &Transform,
+            &TextureAtlasSprite,
+            &Handle<TextureAtlas>,
+            &mut Visibility,
+        ),
+        With<PlayerVelocity>,
+    >,
+    mut deaths: EventReader<PlayerDiedEvent>,
+    settings: Res<DeathSettings>,
+) {
+    for death in deaths.iter() {
+        if let Ok((transform, sprite, atlas, mut visibility)) = player.get_mut(death.player) {
+            *visibility = Visibility::Hidden;
+            let transform = Transform {
+                translation: death.position.extend(transform.translation.z),
+                ..*transform
+            };
+
+            //The parent keeps the player's size since death_animation overwrites the scale
+            commands
+                .spawn((
+                    SpatialBundle::from_transform(transform),
+                    Lifetime {
+                        timer: Timer::from_seconds(settings.animation_time, TimerMode::Once),
+                    },
+                    Name::new("DeathAnimation"),
+                ))
+                .with_children(|commands| {
+                    commands.spawn((
+                        SpriteSheetBundle {
+                            sprite: sprite.clone(),
+                            texture_atlas: atlas.clone(),
+                            ..default()
+                        },
+                        DeathAnimation,
+                    ));
+                });
+
+            spawn_new_rect_emitter(
+                &mut commands,
+                ParticleDesc {
+                    particle: Particle::new(0.6),
+                    sprite: SpriteSheetBundle {
+                        sprite: TextureAtlasSprite {
+                            custom_size: Some(Vec2::splat(8.0)),
+                            ..default()
+                        },
+                        texture_atlas: particle_atlas.0.clone(),
+                        ..default()
+                    },
+                    falling: Some(FallingParticle { speed: 12.0 }),
+                    radial: Some(RadialParticle {
+                        speed: 10.0,
+                        direction: Vec2::ZERO,
+                    }),
+                    rotating: Some(RotatingParticle { speed: 12.0 }),
+                    fading: Some(FadingParticle {}),
+                },
+                transform.translation.truncate(),
+                Vec2::new(20.0, 30.0),
+                Some(0.1),
+                1,
+                Some(0.005),
+            );
+        }
+    }
+}
+
+const MAX_SQUASH: f32 = 0.35;
+/// Landing at this speed gives the full squash
+const FULL_SQUASH_SPEED: f32 = 600.0;
+const SQUASH_TIME: f32 = 0.25;
+const MAX_STRETCH: f32 = 0.15;
+const MAX_LEAN: f32 = 0.15;
+const LEAN_RESPONSE: f32 = 10.0;
+
+fn player_landing_squash(
+    mut commands: Commands,
+    player: Query<Entity, With<Squash>>,
+    mut landings: EventReader<LandedEvent>,
+) {
+    for landing in landings.iter() {
+        if !player.contains(landing.player) {
+            continue;
+        }
+        let amount = (landing.impact_speed / FULL_SQUASH_SPEED).min(1.0) * MAX_SQUASH;
+        commands.entity(landing.player).insert(
+            Squash(Vec2::new(1.0 + amount, 1.0 - amount)).ease_to(
+                Squash::default(),
+                EaseFunction::BackOut,
+                EasingType::Once {
+                    duration: Duration::from_secs_f32(SQUASH_TIME),
+                },
+            ),
+        );
+    }
+}
+
+//Only changes how the player is drawn, player_size puts the rotation back before physics runs
+fn player_squash_stretch(
+    mut player: Query<(
+        &mut TextureAtlasSprite,
+        &mut Transform,
+        &mut Lean,
+        &Squash,
+        &PlayerVelocity,
+        &PlayerStats,
+    )>,
+    table: Res<AnimationTable>,
+    time: Res<Time>,
 ) {
-    for (player, output) in &player {
-        let mut particles = player_particles.single_mut();
-        if output.grounded {
-            particles.force_spawn = player.velocity.x.abs() as usize / 180;
+    for (mut sprite, mut transform, mut lean, squash, velocity, stats) in &mut player {
+        let up = stats.up();
+        let rising = (velocity.velocity.y * up / stats.jump_strength).clamp(0.0, 1.0);
+        let stretch = rising * MAX_STRETCH;
+        let mut scale = squash.0 * Vec2::new(1.0 - stretch, 1.0 + stretch);
+        //Distance from the origin to the feet in sprite heights, crouching moves the origin down
+        let mut feet = 0.5;
+        if velocity.crouching {
+            scale.y *= CROUCH_HEIGHT;
+            feet -= PLAYER_HALF_SIZE.y * (1.0 - CROUCH_HEIGHT) / table.tile_size.y;
         }
+        sprite.custom_size = Some(table.tile_size * scale);
+        //Keeps the feet planted while the height changes
+        sprite.anchor = Anchor::Custom(Vec2::new(0.0, (feet / scale.y - 0.5) * up));
+
+        //The target only changes on simulation ticks so the lean is smoothed over frames
+        let dt = time.delta_seconds();
+        lean.angle += (lean.target - lean.angle) * (LEAN_RESPONSE * dt).min(1.0);
+        transform.rotation = Quat::from_rotation_z(lean.angle * up);
+    }
+}
+
+//Acceleration is measured between ticks, frames in between see no change in velocity
+fn player_lean(
+    mut player: Query<(&mut Lean, &PlayerVelocity, &PlayerStats)>,
+    time: Res<FixedTime>,
+) {
+    for (mut lean, velocity, stats) in &mut player {
+        let acceleration =
+            (velocity.velocity.x - lean.previous_velocity) / time.period.as_secs_f32();
+        lean.target = -(acceleration / stats.player_accel).clamp(-1.0, 1.0) * MAX_LEAN;
+        lean.previous_velocity = velocity.velocity.x;
     }
 }
 
-fn player_animation(mut player: Query<(&mut TextureAtlasSprite, &PlayerVelocity)>) {
-    if let Ok((mut sprite, player)) = player.get_single_mut() {
+fn update_grapple_ropes(
+    mut commands: Commands,
+    player: Query<(Entity, &Transform, &PlayerVelocity), Without<GrappleRope>>,
+    mut ropes: Query<(Entity, &GrappleRope, &mut Transform)>,
+) {
+    for (entity, rope, mut transform) in &mut ropes {
+        match player.get(rope.0).ok().and_then(|(_, player, velocity)| {
+            velocity
+                .grapple
+                .map(|anchor| rope_transform(player.translation.truncate(), anchor))
+        }) {
+            Some(rope) => *transform = rope,
+            None => commands.entity(entity).despawn_recursive(),
+        }
+    }
+
+    for (entity, transform, velocity) in &player {
+        if let Some(anchor) = velocity.grapple {
+            if ropes.iter().any(|(_, rope, _)| rope.0 == entity) {
+                continue;
+            }
+            commands.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::rgb(0.8, 0.7, 0.5),
+                        custom_size: Some(Vec2::new(1.0, 2.0)),
+                        ..default()
+                    },
+                    transform: rope_transform(transform.translation.truncate(), anchor),
+                    ..default()
+                },
+                GrappleRope(entity),
+                Name::new("GrappleRope"),
+            ));
+        }
+    }
+}
+
+//A 1 pixel wide sprite stretched from the player to the anchor
+fn rope_transform(position: Vec2, anchor: Vec2) -> Transform {
+    let offset = anchor - position;
+    Transform {
+        translation: ((position + anchor) / 2.0).extend(CHARACTER_Z - 1.0),
+        rotation: Quat::from_rotation_z(offset.y.atan2(offset.x)),
+        scale: Vec3::new(offset.length(), 1.0, 1.0),
+    }
+}
+
+fn player_animation(
+    mut player: Query<(
+        &mut TextureAtlasSprite,
+        &mut CharacterAnimation,
+        &PlayerVelocity,
+        &PlayerStats,
+        &PlayerState,
+    )>,
+) {
+    for (mut sprite, mut animation, player, stats, state) in &mut player {
         if player.velocity.x > 0.0 {
             sprite.flip_x = true;
         }
         if player.velocity.x < 0.0 {
             sprite.flip_x = false;
         }
+        sprite.flip_y = stats.gravity_flipped;
+
+        animation.play(match state {
+            PlayerState::Grounded | PlayerState::Dashing if player.velocity.x.abs() > 1.0 => {
+                SpriteAnimation::Run
+            }
+            PlayerState::Rising | PlayerState::Swinging => SpriteAnimation::Jump,
+            PlayerState::Falling | PlayerState::Gliding => SpriteAnimation::Fall,
+            PlayerState::WallSliding => SpriteAnimation::WallSlide,
+            PlayerState::Crouching => SpriteAnimation::Crouch,
+            PlayerState::Sliding => SpriteAnimation::Slide,
+            PlayerState::Dead => SpriteAnimation::Death,
+            _ => SpriteAnimation::Idle,
+        });
+    }
+}
+
+const PARTICLE_OFFSET: f32 = 13.0;
+
+//Transform scale also scales the collider and the particle offsets
+fn player_size(
+    mut player: Query<(&mut Transform, &PlayerStats), With<PlayerVelocity>>,
+    mut head_particles: Query<
+        &mut Transform,
+        (
+            With<PlayerHeadParticles>,
+            Without<PlayerFeetParticles>,
+            Without<PlayerVelocity>,
+        ),
+    >,
+    mut feet_particles: Query<
+        &mut Transform,
+        (
+            With<PlayerFeetParticles>,
+            Without<PlayerHeadParticles>,
+            Without<PlayerVelocity>,
+        ),
+    >,
+) {
+    for (mut transform, stats) in &mut player {
+        let scale = Vec3::new(stats.size, stats.size, 1.0);
+        if transform.scale != scale {
+            transform.scale = scale;
+        }
+        //Leaning is only drawn, the collider stays upright
+        if transform.rotation != Quat::IDENTITY {
+            transform.rotation = Quat::IDENTITY;
+        }
+        for mut head in &mut head_particles {
+            head.translation.y = PARTICLE_OFFSET * stats.up();
+        }
+        for mut feet in &mut feet_particles {
+            feet.translation.y = -PARTICLE_OFFSET * stats.up();
+        }
     }
 }
 
 fn player_gravity(
-    mut player: Query<(&mut PlayerVelocity, &PlayerStats)>,
-    keyboard: Res<Input<KeyCode>>,
-    time: Res<Time>,
+    mut player: Query<(&mut PlayerVelocity, &PlayerStats, &TickActions)>,
+    time: Res<FixedTime>,
+    mut wall_slide_writer: EventWriter<WallSlideEvent>,
+    mut glide_writer: EventWriter<GlideEvent>,
 ) {
-    for (mut velocity, stats) in player.iter_mut() {
-        if keyboard.pressed(KeyCode::Space) {
-            velocity.velocity += Vec2::new(0.0, stats.float_gravity * time.delta_seconds());
+    for (mut velocity, stats, actions) in player.iter_mut() {
+        if velocity.climbing {
+            velocity.wall_sliding = false;
+            velocity.gliding = false;
+            continue;
+        }
+        let up = stats.up();
+        if actions.just_released(Action::Jump) && velocity.velocity.y * up > 0.0 {
+            velocity.velocity.y *= stats.jump_cut_multiplier;
+        }
+        if actions.pressed(Action::Jump) {
+            velocity.velocity +=
+                Vec2::new(0.0, up * stats.float_gravity * time.period.as_secs_f32());
         } else {
-            velocity.velocity += Vec2::new(0.0, stats.true_gravity * time.delta_seconds());
+            velocity.velocity +=
+                Vec2::new(0.0, up * stats.true_gravity * time.period.as_secs_f32());
+        }
+
+        let fall_speed = -velocity.velocity.y * up;
+        let wall_sliding = stats.wall_slide_friction > 0.0
+            && velocity.on_wall != OnWall::NotOnWall
+            && velocity.time_since_wall == 0.0
+            && fall_speed > 0.0;
+        if wall_sliding {
+            let fall_speed =
+                (fall_speed - stats.wall_slide_friction * time.period.as_secs_f32()).max(0.0);
+            velocity.velocity.y = -fall_speed * up;
+            if !velocity.wall_sliding {
+                wall_slide_writer.send(WallSlideEvent);
+            }
+        }
+        velocity.wall_sliding = wall_sliding;
+
+        let gliding = stats.glide_speed > 0.0
+            && actions.pressed(Action::Jump)
+            && -velocity.velocity.y * up > stats.glide_speed;
+        if gliding {
+            velocity.velocity.y = -stats.glide_speed * up;
+            if !velocity.gliding {
+                glide_writer.send(GlideEvent);
+            }
+        }
+        velocity.gliding = gliding;
+    }
+}
+
+//Crouching shrinks the collider towards the feet, standing up needs room above
+fn player_crouch(
+    mut player: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut Collider,
+            &mut PlayerVelocity,
+            &PlayerStats,
+            &PlayerState,
+            &KinematicCharacterControllerOutput,
+            &TickActions,
+        ),
+        Without<RespawnTimer>,
+    >,
+    rapier_context: Res<RapierContext>,
+    time: Res<FixedTime>,
+) {
+    for (entity, mut transform, mut collider, mut velocity, stats, state, output, actions) in
+        &mut player
+    {
+        let wants_crouch = state.has_control()
+            && output.grounded
+            && !velocity.climbing
+            && velocity.dash_timer <= 0.0
+            && actions.pressed(Action::MoveDown);
+        //How far the center moves so the feet stay in place
+        let shift = PLAYER_HALF_SIZE.y * (1.0 - CROUCH_HEIGHT) * stats.size * stats.up();
+
+        if wants_crouch && !velocity.crouching {
+            velocity.crouching = true;
+            transform.translation.y -= shift;
+            *collider = player_collider(true);
+            if stats.slide_time > 0.0
+                && velocity.velocity.x.abs() > stats.player_max_velocity * SLIDE_SPEED
+            {
+                velocity.slide_timer = stats.slide_time;
+            }
+        } else if !wants_crouch && velocity.crouching {
+            //Slightly smaller than the standing collider so the floor and walls don't count
+            let standing = transform.translation.truncate() + Vec2::new(0.0, shift);
+            let shape = Collider::cuboid(
+                (PLAYER_HALF_SIZE.x - 1.0) * stats.size,
+                (PLAYER_HALF_SIZE.y - 1.0) * stats.size,
+            );
+            let filter = QueryFilter::default()
+                .exclude_sensors()
+                .exclude_collider(entity)
+                .groups(player_filter_groups());
+            if rapier_context
+                .intersection_with_shape(standing, 0.0, &shape, filter)
+                .is_none()
+            {
+                velocity.crouching = false;
+                transform.translation.y += shift;
+                *collider = player_collider(false);
+            }
+        }
+
+        if !velocity.crouching {
+            velocity.slide_timer = 0.0;
+        }
+        if velocity.slide_timer > 0.0 {
+            velocity.slide_timer -= time.period.as_secs_f32();
         }
     }
 }
 
 fn player_control(
-    mut player: Query<(&mut PlayerVelocity, &PlayerStats)>,
-    keyboard: Res<Input<KeyCode>>,
-    time: Res<Time>,
+    mut player: Query<(
+        &mut PlayerVelocity,
+        &PlayerStats,
+        &PlayerState,
+        &TickActions,
+    )>,
+    time: Res<FixedTime>,
 ) {
-    for (mut velocity, stats) in player.iter_mut() {
-        if keyboard.pressed(KeyCode::A) {
-            velocity.velocity += Vec2::new(-stats.player_accel * time.delta_seconds(), 0.0);
+    for (mut velocity, stats, state, actions) in player.iter_mut() {
+        //Slides keep their momentum and ignore steering until they end
+        if velocity.slide_timer > 0.0 {
+            let friction =
+                -stats.slide_friction * velocity.velocity.x.signum() * time.period.as_secs_f32();
+            if velocity.velocity.x.abs() < friction.abs() {
+                velocity.velocity.x = 0.0;
+                velocity.slide_timer = 0.0;
+            } else {
+                velocity.velocity.x += friction;
+            }
+            continue;
+        }
+
+        let speed_before = velocity.velocity.x.abs();
+        let swinging = velocity.grapple.is_some();
+        let left = state.has_control() && actions.pressed(Action::MoveLeft);
+        let right = state.has_control() && actions.pressed(Action::MoveRight);
+        if left {
+            velocity.velocity += Vec2::new(-stats.player_accel * time.period.as_secs_f32(), 0.0);
         }
-        if keyboard.pressed(KeyCode::D) {
-            velocity.velocity += Vec2::new(stats.player_accel * time.delta_seconds(), 0.0);
+        if right {
+            velocity.velocity += Vec2::new(stats.player_accel * time.period.as_secs_f32(), 0.0);
         }
-        if !keyboard.pressed(KeyCode::A) && !keyboard.pressed(KeyCode::D) {
+        if !left && !right && !swinging {
             //TODO time dependent slow down?
             //FIXME gives the shakes
             let deccel_amount =
-                -stats.player_deccel * velocity.velocity.x.signum() * time.delta_seconds();
+                -stats.player_deccel * velocity.velocity.x.signum() * time.period.as_secs_f32();
             if velocity.velocity.x.abs() < deccel_amount.abs() {
                 velocity.velocity.x = 0.0;
             } else {
@@ -250,75 +1096,376 @@ fn player_control(
             }
         }
 
-        velocity.velocity.x = velocity
-            .velocity
-            .x
-            .clamp(-stats.player_max_velocity, stats.player_max_velocity);
+        //Left and right pump the swing, the rope handles the rest
+        if swinging {
+            velocity.swing_momentum = true;
+            continue;
+        }
+
+        let max_velocity = if velocity.crouching {
+            stats.player_max_velocity * SLIDE_SPEED
+        } else {
+            stats.player_max_velocity
+        };
+        if speed_before <= max_velocity {
+            velocity.swing_momentum = false;
+        }
+        let max_velocity = if velocity.swing_momentum {
+            (speed_before - stats.player_deccel * time.period.as_secs_f32()).max(max_velocity)
+        } else {
+            max_velocity
+        };
+        velocity.velocity.x = velocity.velocity.x.clamp(-max_velocity, max_velocity);
     }
 }
 
+const WALL_CHECK_DISTANCE: f32 = 2.0;
+
+fn touching_wall(
+    rapier_context: &RapierContext,
+    player: Entity,
+    position: Vec2,
+    direction: f32,
+    size: f32,
+) -> bool {
+    //Slightly shorter than the player so floors and ceilings don't count as walls
+    let shape = Collider::cuboid(17.0 / 2.0 * size, 12.0 * size);
+    let filter = QueryFilter::default()
+        .exclude_sensors()
+        .exclude_collider(player)
+        .groups(player_filter_groups());
+    rapier_context
+        .cast_shape(
+            position,
+            0.0,
+            Vec2::new(direction * WALL_CHECK_DISTANCE, 0.0),
+            &shape,
+            1.0,
+            filter,
+        )
+        .is_some()
+}
+
+/// Keeps a grounded player pushing into the floor so the controller keeps reporting ground
+const GROUND_STICK_SPEED: f32 = 0.1;
+
+#[allow(clippy::too_many_arguments)]
 fn player_jump(
     mut controllers: Query<(
+        Entity,
+        &Transform,
         &KinematicCharacterControllerOutput,
         &mut PlayerVelocity,
         &PlayerStats,
+        &PlayerState,
+        &TickActions,
+        &Children,
     )>,
     mut writer: EventWriter<JumpEvent>,
+    mut wall_jump_writer: EventWriter<WallJumpEvent>,
+    mut air_jump_writer: EventWriter<AirJumpEvent>,
     mut player_particles: Query<&mut RectParticleEmitter, With<PlayerHeadParticles>>,
-    keyboard: Res<Input<KeyCode>>,
+    mut player_feet_particles: Query<
+        &mut RectParticleEmitter,
+        (With<PlayerFeetParticles>, Without<PlayerHeadParticles>),
+    >,
+    rapier_context: Res<RapierContext>,
+    time: Res<FixedTime>,
 ) {
-    for (controller, mut velocity, stats) in controllers.iter_mut() {
-        if controller.desired_translation.y - controller.effective_translation.y > 0.1 {
-            let mut particles = player_particles.single_mut();
-            particles.force_spawn = 6;
-            velocity.velocity.y = -0.1;
+    for (entity, transform, controller, mut velocity, stats, state, actions, children) in
+        controllers.iter_mut()
+    {
+        let up = stats.up();
+        //Hit a ceiling, which is the floor when gravity is flipped
+        if (controller.desired_translation.y - controller.effective_translation.y) * up > 0.1 {
+            if let Some(mut particles) = child_emitter(children, &mut player_particles) {
+                particles.force_spawn = 6;
+            }
+            velocity.velocity.y = 0.0;
         }
         if (controller.desired_translation.x - controller.effective_translation.x).abs() > 0.02 {
-            if !controller.grounded {
-                if controller.desired_translation.x - controller.effective_translation.x > 0.0 {
-                    velocity.last_on_wall = 0;
-                    velocity.on_wall = OnWall::OnLeft;
-                } else {
-                    velocity.last_on_wall = 0;
-                    velocity.on_wall = OnWall::OnRight;
-                }
-            } else {
-                velocity.last_on_wall += 1;
-                velocity.on_wall = OnWall::NotOnWall;
-            }
             velocity.velocity.x = 0.0;
-        } else if controller.desired_translation.x.abs() > 0.02 {
-            velocity.last_on_wall += 1;
-            velocity.on_wall = OnWall::NotOnWall;
         }
+
+        let position = transform.translation.truncate();
         if controller.grounded {
-            velocity.velocity.y = -0.1;
-            if keyboard.just_pressed(KeyCode::Space) {
-                velocity.velocity += Vec2::new(0.0, stats.jump_strength);
-                writer.send(JumpEvent);
-            }
-        } else if stats.can_wall_jump
-            && velocity.last_on_wall < 6
-            && keyboard.just_pressed(KeyCode::Space)
+            velocity.on_wall = OnWall::NotOnWall;
+            velocity.time_since_wall += time.period.as_secs_f32();
+        } else if touching_wall(&rapier_context, entity, position, -1.0, stats.size) {
+            velocity.on_wall = OnWall::OnLeft;
+            velocity.time_since_wall = 0.0;
+        } else if touching_wall(&rapier_context, entity, position, 1.0, stats.size) {
+            velocity.on_wall = OnWall::OnRight;
+            velocity.time_since_wall = 0.0;
+        } else {
+            velocity.time_since_wall += time.period.as_secs_f32();
+        }
+
+        if state.is_grounded() || velocity.climbing {
+            velocity.velocity.y = -GROUND_STICK_SPEED * up;
+        }
+        if controller.grounded || velocity.climbing {
+            velocity.coyote_timer = stats.coyote_time;
+            velocity.air_jumps_used = 0;
+        } else {
+            velocity.coyote_timer -= time.period.as_secs_f32();
+        }
+        if actions.just_pressed(Action::Jump) {
+            velocity.jump_buffer = stats.jump_buffer;
+        } else {
+            velocity.jump_buffer -= time.period.as_secs_f32();
+        }
+
+        let wants_jump = state.has_control()
+            && (actions.just_pressed(Action::Jump) || velocity.jump_buffer > 0.0);
+        let can_ground_jump =
+            controller.grounded || velocity.climbing || velocity.coyote_timer > 0.0;
+
+        if wants_jump && can_ground_jump {
+            velocity.climbing = false;
+            velocity.coyote_timer = 0.0;
+            velocity.jump_buffer = 0.0;
+            velocity.velocity.y = up * stats.jump_strength;
+            writer.send(JumpEvent);
+        } else if wants_jump
+            && stats.can_wall_jump
+            && velocity.on_wall != OnWall::NotOnWall
+            && velocity.time_since_wall < stats.wall_jump_grace
+        {
+            velocity.jump_buffer = 0.0;
+            //Jump away from the wall
+            let direction = if velocity.on_wall == OnWall::OnLeft {
+                1.0
+            } else {
+                -1.0
+            };
+            velocity.on_wall = OnWall::NotOnWall;
+            velocity.velocity.x += direction * stats.wall_jump_strength;
+            velocity.velocity.y = up * stats.jump_strength;
+            wall_jump_writer.send(WallJumpEvent);
+        } else if state.has_control()
+            && actions.just_pressed(Action::Jump)
+            && velocity.air_jumps_used < stats.air_jumps
+        {
+            velocity.jump_buffer = 0.0;
+            velocity.air_jumps_used += 1;
+            velocity.velocity.y = up * stats.jump_strength;
+            if let Some(mut particles) = child_emitter(children, &mut player_feet_particles) {
+                particles.force_spawn = 6;
+            }
+            air_jump_writer.send(AirJumpEvent);
+        }
+    }
+}
+
+fn player_climb(
+    mut player: Query<(&Transform, &mut PlayerVelocity, &PlayerStats, &TickActions)>,
+    ladders: Query<(), With<Ladder>>,
+    rapier_context: Res<RapierContext>,
+) {
+    for (transform, mut velocity, stats, actions) in &mut player {
+        let shape = Collider::cuboid(4.0 * stats.size, 14.0 * stats.size);
+        let shape_pos = transform.translation.truncate();
+        let filter = QueryFilter::default();
+
+        let mut on_ladder = false;
+        rapier_context.intersections_with_shape(shape_pos, 0.0, &shape, filter, |entity| {
+            on_ladder = ladders.contains(entity);
+            !on_ladder
+        });
+
+        if !on_ladder {
+            velocity.climbing = false;
+            continue;
+        }
+
+        //Only grab on the way down so jumping off a ladder doesn't instantly regrab it
+        let up = actions.pressed(Action::MoveUp);
+        let down = actions.pressed(Action::MoveDown);
+        if (up && velocity.velocity.y * stats.up() <= 0.0) || down {
+            velocity.climbing = true;
+        }
+
+        if velocity.climbing {
+            velocity.velocity.y = 0.0;
+            if up {
+                velocity.velocity.y += stats.climb_speed;
+            }
+            if down {
+                velocity.velocity.y -= stats.climb_speed;
+            }
+        }
+    }
+}
+
+fn player_push(
+    player: Query<(&KinematicCharacterControllerOutput, &PlayerStats)>,
+    mut crates: Query<(&mut ExternalImpulse, &Velocity), With<Crate>>,
+    time: Res<FixedTime>,
+) {
+    for (output, stats) in &player {
+        for collision in &output.collisions {
+            if let Ok((mut impulse, crate_velocity)) = crates.get_mut(collision.entity) {
+                //Don't push crates we are only standing on
+                if collision.translation_remaining.x.abs() < 0.01 {
+                    continue;
+                }
+                let direction = collision.translation_remaining.x.signum();
+                //Impulse is in pixels so crate mass scales how hard it is to shove
+                if crate_velocity.linvel.x * direction < stats.player_max_velocity {
+                    impulse.impulse.x +=
+                        direction * stats.push_strength * time.period.as_secs_f32();
+                }
+            }
+        }
+    }
+}
+
+fn player_dash(
+    mut player: Query<(
+        &mut PlayerVelocity,
+        &PlayerStats,
+        &PlayerState,
+        &TickActions,
+        &Children,
+    )>,
+    mut player_particles: Query<&mut RectParticleEmitter, With<PlayerFeetParticles>>,
+    mut writer: EventWriter<DashEvent>,
+    time: Res<FixedTime>,
+) {
+    for (mut velocity, stats, state, actions, children) in &mut player {
+        velocity.dash_cooldown -= time.period.as_secs_f32();
+
+        if stats.dash_speed > 0.0
+            && state.has_control()
+            && actions.just_pressed(Action::Dash)
+            && velocity.dash_cooldown <= 0.0
         {
-            if velocity.on_wall == OnWall::OnLeft {
-                velocity.velocity.y = -0.1;
-                velocity.velocity += Vec2::new(-stats.wall_jump_strength, stats.jump_strength);
-                writer.send(JumpEvent);
+            let mut direction = Vec2::ZERO;
+            if actions.pressed(Action::MoveLeft) {
+                direction.x -= 1.0;
+            }
+            if actions.pressed(Action::MoveRight) {
+                direction.x += 1.0;
+            }
+            if actions.pressed(Action::MoveUp) {
+                direction.y += 1.0;
+            }
+            if actions.pressed(Action::MoveDown) {
+                direction.y -= 1.0;
+            }
+            //Dash the way the player is moving when no direction is held
+            if direction == Vec2::ZERO {
+                direction.x = if velocity.velocity.x < 0.0 { -1.0 } else { 1.0 };
+            }
+            velocity.dash_direction = direction.normalize();
+            velocity.dash_timer = stats.dash_time;
+            velocity.dash_cooldown = stats.dash_cooldown;
+            velocity.climbing = false;
+            if let Some(mut particles) = child_emitter(children, &mut player_particles) {
+                particles.force_spawn = 8;
+            }
+            writer.send(DashEvent);
+        }
+
+        if velocity.dash_timer > 0.0 {
+            velocity.dash_timer -= time.period.as_secs_f32();
+            if velocity.dash_timer > 0.0 && state.has_control() {
+                velocity.velocity = velocity.dash_direction * stats.dash_speed;
             } else {
-                velocity.velocity.y = -0.1;
-                velocity.velocity += Vec2::new(stats.wall_jump_strength, stats.jump_strength);
-                writer.send(JumpEvent);
+                velocity.dash_timer = 0.0;
+                velocity.velocity = velocity.dash_direction * stats.player_max_velocity;
+            }
+        }
+    }
+}
+
+fn player_grapple(
+    mut player: Query<(
+        &Transform,
+        &mut PlayerVelocity,
+        &PlayerStats,
+        &PlayerState,
+        &TickActions,
+    )>,
+    rapier_context: Res<RapierContext>,
+    time: Res<FixedTime>,
+) {
+    for (transform, mut velocity, stats, state, actions) in &mut player {
+        let dt = time.period.as_secs_f32();
+        let position = transform.translation.truncate();
+        velocity.grapple_cooldown -= dt;
+
+        if !state.has_control() || !actions.pressed(Action::Grapple) {
+            velocity.grapple = None;
+        }
+
+        if stats.grapple_range > 0.0
+            && state.has_control()
+            && actions.just_pressed(Action::Grapple)
+            && velocity.grapple_cooldown <= 0.0
+        {
+            //Aimed with the held directions, up and forward when nothing is held
+            let mut direction = Vec2::ZERO;
+            if actions.pressed(Action::MoveLeft) {
+                direction.x -= 1.0;
+            }
+            if actions.pressed(Action::MoveRight) {
+                direction.x += 1.0;
+            }
+            if actions.pressed(Action::MoveUp) {
+                direction.y += 1.0;
+            }
+            if actions.pressed(Action::MoveDown) {
+                direction.y -= 1.0;
+            }
+            if direction == Vec2::ZERO {
+                direction.x = if velocity.velocity.x < 0.0 { -1.0 } else { 1.0 };
+                direction.y = stats.up();
+            }
+            let direction = direction.normalize();
+
+            //Only the map's fixed colliders hold a rope
+            let filter = QueryFilter::only_fixed().exclude_sensors();
+            if let Some((_, distance)) =
+                rapier_context.cast_ray(position, direction, stats.grapple_range, true, filter)
+            {
+                velocity.grapple = Some(position + direction * distance);
+                velocity.grapple_length = distance;
+                velocity.grapple_cooldown = stats.grapple_cooldown;
+                velocity.climbing = false;
+            }
+        }
+
+        //A taut rope bends the velocity so the next position stays on the circle, which swings
+        if let Some(anchor) = velocity.grapple {
+            let offset = position + velocity.velocity * dt - anchor;
+            if offset.length() > velocity.grapple_length {
+                let constrained = anchor + offset.normalize() * velocity.grapple_length;
+                velocity.velocity = (constrained - position) / dt;
             }
         }
     }
 }
 
 fn player_update(
-    mut controllers: Query<(&mut KinematicCharacterController, &PlayerVelocity)>,
-    time: Res<Time>,
+    mut controllers: Query<(
+        &mut KinematicCharacterController,
+        &PlayerVelocity,
+        &PlayerStats,
+        Option<&RespawnTimer>,
+        Option<&ReachedExit>,
+    )>,
+    time: Res<FixedTime>,
 ) {
-    for (mut controller, velocity) in controllers.iter_mut() {
-        controller.translation = Some(velocity.velocity * time.delta_seconds());
+    for (mut controller, velocity, stats, dead, waiting) in controllers.iter_mut() {
+        //Grounded checks and slopes follow the direction of gravity
+        controller.up = Vec2::Y * stats.up();
+        //The player stays where they died until they respawn, or inside the door until the level ends
+        if dead.is_some() || waiting.is_some() {
+            controller.translation = None;
+            continue;
+        }
+        controller.translation = Some(velocity.velocity * time.period.as_secs_f32());
     }
 }
@@ -0,0 +1,149 @@
+use crate::prelude::*;
+
+pub struct PlayerStatePlugin;
+
+impl Plugin for PlayerStatePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PlayerStateChanged>().add_systems(
+            (update_player_state, detect_landings)
+                .chain()
+                .after(PlayerCheckSet)
+                .in_schedule(CoreSchedule::FixedUpdate),
+        );
+    }
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum PlayerState {
+    #[default]
+    Grounded,
+    Rising,
+    Falling,
+    WallSliding,
+    Gliding,
+    Dashing,
+    Swinging,
+    Climbing,
+    Crouching,
+    Sliding,
+    Dead,
+    Exiting,
+    InCutscene,
+}
+
+impl PlayerState {
+    /// Whether movement input should be applied in this state
+    pub fn has_control(&self) -> bool {
+        !matches!(
+            self,
+            PlayerState::Dead | PlayerState::Exiting | PlayerState::InCutscene
+        )
+    }
+
+    /// Standing on the floor, whether crouched or not
+    pub fn is_grounded(&self) -> bool {
+        matches!(
+            self,
+            PlayerState::Grounded | PlayerState::Crouching | PlayerState::Sliding
+        )
+    }
+
+    fn is_airborne(&self) -> bool {
+        matches!(
+            self,
+            PlayerState::Rising
+                | PlayerState::Falling
+                | PlayerState::WallSliding
+                | PlayerState::Gliding
+                | PlayerState::Swinging
+        )
+    }
+}
+
+pub struct PlayerStateChanged {
+    pub entity: Entity,
+    pub from: PlayerState,
+    pub to: PlayerState,
+}
+
+fn update_player_state(
+    mut player: Query<(
+        Entity,
+        &mut PlayerState,
+        &PlayerVelocity,
+        &PlayerStats,
+        Option<&KinematicCharacterControllerOutput>,
+        Option<&RespawnTimer>,
+        Option<&ExitTimer>,
+        Option<&ReachedExit>,
+    )>,
+    game_state: Res<State<GameState>>,
+    mut writer: EventWriter<PlayerStateChanged>,
+) {
+    for (entity, mut state, velocity, stats, output, dying, exiting, waiting) in &mut player {
+        //Rising and falling are relative to gravity
+        let vertical = velocity.velocity.y * stats.up();
+        let grounded = output.is_some_and(|output| output.grounded);
+
+        let next = if game_state.0 == GameState::Paused {
+            *state
+        } else if game_state.0 == GameState::Cutscene {
+            PlayerState::InCutscene
+        } else if dying.is_some() {
+            PlayerState::Dead
+        } else if exiting.is_some() || waiting.is_some() {
+            PlayerState::Exiting
+        } else if velocity.dash_timer > 0.0 {
+            PlayerState::Dashing
+        } else if velocity.grapple.is_some() {
+            PlayerState::Swinging
+        } else if velocity.climbing {
+            PlayerState::Climbing
+        } else if grounded && velocity.slide_timer > 0.0 {
+            PlayerState::Sliding
+        } else if grounded && velocity.crouching {
+            PlayerState::Crouching
+        } else if grounded {
+            PlayerState::Grounded
+        } else if velocity.on_wall != OnWall::NotOnWall
+            && velocity.time_since_wall == 0.0
+            && vertical <= 0.0
+        {
+            PlayerState::WallSliding
+        } else if velocity.gliding {
+            PlayerState::Gliding
+        } else if vertical > 0.0 {
+            PlayerState::Rising
+        } else {
+            PlayerState::Falling
+        };
+
+        if next != *state {
+            writer.send(PlayerStateChanged {
+                entity,
+                from: *state,
+                to: next,
+            });
+            *state = next;
+        }
+    }
+}
+
+fn detect_landings(
+    player: Query<(&PlayerVelocity, &PlayerStats)>,
+    mut changes: EventReader<PlayerStateChanged>,
+    mut landed: EventWriter<LandedEvent>,
+) {
+    for change in changes.iter() {
+        if !change.from.is_airborne() || change.to != PlayerState::Grounded {
+            continue;
+        }
+        if let Ok((velocity, stats)) = player.get(change.entity) {
+            //Velocity isn't reset until the next jump check so it still holds the fall speed
+            landed.send(LandedEvent {
+                player: change.entity,
+                impact_speed: (-velocity.velocity.y * stats.up()).max(0.0),
+            });
+        }
+    }
+}
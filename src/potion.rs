@@ -0,0 +1,326 @@
+use std::collections::VecDeque;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+pub struct PotionPlugin;
+
+impl Plugin for PotionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DistortionState>()
+            .add_system(
+                update_potion_effects
+                    .before(PlayerMovementSet)
+                    .run_if(simulation_running)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(
+                distort_actions
+                    .after(update_potion_effects)
+                    .after(update_tick_actions)
+                    .after(ReplaySet)
+                    .before(PlayerMovementSet)
+                    .run_if(simulation_running)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            );
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Modifier {
+    Set(f32),
+    Add(f32),
+    Mul(f32),
+}
+
+impl Modifier {
+    pub fn apply(&self, value: f32) -> f32 {
+        match self {
+            Modifier::Set(set) => *set,
+            Modifier::Add(add) => value + add,
+            Modifier::Mul(mul) => value * mul,
+        }
+    }
+}
+
+/// Changes to `PlayerStats`, fields left out are untouched
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatModifiers {
+    pub float_gravity: Option<Modifier>,
+    pub true_gravity: Option<Modifier>,
+    pub player_accel: Option<Modifier>,
+    pub player_deccel: Option<Modifier>,
+    pub player_max_velocity: Option<Modifier>,
+    pub jump_strength: Option<Modifier>,
+    pub can_wall_jump: Option<bool>,
+    pub wall_jump_strength: Option<Modifier>,
+    pub wall_jump_grace: Option<Modifier>,
+    pub push_strength: Option<Modifier>,
+    pub climb_speed: Option<Modifier>,
+    pub coyote_time: Option<Modifier>,
+    pub jump_buffer: Option<Modifier>,
+    pub jump_cut_multiplier: Option<Modifier>,
+    pub dash_speed: Option<Modifier>,
+    pub dash_time: Option<Modifier>,
+    pub dash_cooldown: Option<Modifier>,
+    pub air_jumps: Option<Modifier>,
+    pub wall_slide_friction: Option<Modifier>,
+    pub glide_speed: Option<Modifier>,
+    pub slide_friction: Option<Modifier>,
+    pub slide_time: Option<Modifier>,
+    pub grapple_range: Option<Modifier>,
+    pub grapple_cooldown: Option<Modifier>,
+    pub rewind_time: Option<Modifier>,
+    pub gravity_flipped: Option<bool>,
+    pub size: Option<Modifier>,
+}
+
+fn modify(value: &mut f32, modifier: &Option<Modifier>) {
+    if let Some(modifier) = modifier {
+        *value = modifier.apply(*value);
+    }
+}
+
+impl StatModifiers {
+    pub fn apply(&self, stats: &mut PlayerStats) {
+        modify(&mut stats.float_gravity, &self.float_gravity);
+        modify(&mut stats.true_gravity, &self.true_gravity);
+        modify(&mut stats.player_accel, &self.player_accel);
+        modify(&mut stats.player_deccel, &self.player_deccel);
+        modify(&mut stats.player_max_velocity, &self.player_max_velocity);
+        modify(&mut stats.jump_strength, &self.jump_strength);
+        if let Some(can_wall_jump) = self.can_wall_jump {
+            stats.can_wall_jump = can_wall_jump;
+        }
+        modify(&mut stats.wall_jump_strength, &self.wall_jump_strength);
+        modify(&mut stats.wall_jump_grace, &self.wall_jump_grace);
+        modify(&mut stats.push_strength, &self.push_strength);
+        modify(&mut stats.climb_speed, &self.climb_speed);
+        modify(&mut stats.coyote_time, &self.coyote_time);
+        modify(&mut stats.jump_buffer, &self.jump_buffer);
+        modify(&mut stats.jump_cut_multiplier, &self.jump_cut_multiplier);
+        modify(&mut stats.dash_speed, &self.dash_speed);
+        modify(&mut stats.dash_time, &self.dash_time);
+        modify(&mut stats.dash_cooldown, &self.dash_cooldown);
+        if let Some(modifier) = self.air_jumps {
+            stats.air_jumps = modifier.apply(stats.air_jumps as f32).round().max(0.0) as u32;
+        }
+        modify(&mut stats.wall_slide_friction, &self.wall_slide_friction);
+        modify(&mut stats.glide_speed, &self.glide_speed);
+        modify(&mut stats.slide_friction, &self.slide_friction);
+        modify(&mut stats.slide_time, &self.slide_time);
+        modify(&mut stats.grapple_range, &self.grapple_range);
+        modify(&mut stats.grapple_cooldown, &self.grapple_cooldown);
+        modify(&mut stats.rewind_time, &self.rewind_time);
+        if let Some(gravity_flipped) = self.gravity_flipped {
+            stats.gravity_flipped = gravity_flipped;
+        }
+        modify(&mut stats.size, &self.size);
+    }
+}
+
+/// Effects on the player's input, applied to `TickActions` before any movement system sees them
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum ControlDistortion {
+    InvertHorizontal,
+    /// Delays every action by this many simulation ticks
+    Latency(u32),
+    /// Every this many seconds jump and up have a chance to swap
+    SwapJump(f32),
+    /// Mirrors the screen along with left and right
+    Mirror,
+}
+
+/// A potion as loaded from `assets/potions`
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PotionEffect {
+    pub modifiers: StatModifiers,
+    pub distortions: Vec<ControlDistortion>,
+    /// Seconds until the effect wears off, forever if left out
+    pub duration: Option<f32>,
+}
+
+/// Potions the player has drunk, applied in order over the base stats
+#[derive(Component)]
+pub struct PotionEffects {
+    pub base: PlayerStats,
+    pub active: Vec<(PotionEffect, Option<f32>)>,
+}
+
+impl PotionEffects {
+    pub fn new(base: PlayerStats) -> Self {
+        Self {
+            base,
+            active: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, effect: PotionEffect) {
+        let duration = effect.duration;
+        self.active.push((effect, duration));
+    }
+
+    pub fn distortions(&self) -> impl Iterator<Item = &ControlDistortion> {
+        self.active
+            .iter()
+            .flat_map(|(effect, _)| effect.distortions.iter())
+    }
+
+    pub fn mirrored(&self) -> bool {
+        self.distortions()
+            .any(|distortion| *distortion == ControlDistortion::Mirror)
+    }
+
+    pub fn stats(&self) -> PlayerStats {
+        let mut stats = self.base;
+        for (effect, _) in &self.active {
+            effect.modifiers.apply(&mut stats);
+        }
+        stats
+    }
+}
+
+fn update_potion_effects(
+    mut player: Query<(&mut PotionEffects, &mut PlayerStats)>,
+    time: Res<FixedTime>,
+) {
+    for (mut effects, mut stats) in &mut player {
+        let count = effects.active.len();
+        for (_, remaining) in &mut effects.active {
+            if let Some(remaining) = remaining {
+                *remaining -= time.period.as_secs_f32();
+            }
+        }
+        effects
+            .active
+            .retain(|(_, remaining)| remaining.is_none_or(|remaining| remaining > 0.0));
+        if effects.active.len() != count {
+            info!("Potion effect wore off");
+            *stats = effects.stats();
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct DistortionState {
+    /// Recent action masks of each player, keyed by `PlayerIndex`
+    history: HashMap<usize, VecDeque<u32>>,
+    //Seeded from the run so replays see the same swaps
+    rng: Option<StdRng>,
+    swap_timer: f32,
+    jump_swapped: bool,
+}
+
+fn distort_actions(
+    mut player: Query<(&PlayerIndex, &PotionEffects, &mut TickActions)>,
+    mut state: ResMut<DistortionState>,
+    seed: Res<RunSeed>,
+    time: Res<FixedTime>,
+) {
+    //Every player drinks the same potions so the jump swap is shared
+    let swap_interval = player
+        .iter()
+        .flat_map(|(_, effects, _)| effects.distortions())
+        .filter_map(|distortion| match distortion {
+            ControlDistortion::SwapJump(interval) => Some(*interval),
+            _ => None,
+        })
+        .last();
+
+    let state = &mut *state;
+    if let Some(interval) = swap_interval {
+        state.swap_timer -= time.period.as_secs_f32();
+        if state.swap_timer <= 0.0 {
+            state.swap_timer = interval;
+            let rng = state
+                .rng
+                .get_or_insert_with(|| StdRng::seed_from_u64(seed.0));
+            state.jump_swapped = rng.gen_bool(0.5);
+        }
+    } else {
+        state.jump_swapped = false;
+    }
+
+    for (index, effects, mut actions) in &mut player {
+        let mut latency = 0;
+        let mut inverted = false;
+        for distortion in effects.distortions() {
+            match distortion {
+                ControlDistortion::InvertHorizontal | ControlDistortion::Mirror => {
+                    inverted = !inverted
+                }
+                ControlDistortion::Latency(ticks) => latency = latency.max(*ticks as usize),
+                ControlDistortion::SwapJump(_) => {}
+            }
+        }
+
+        let history = state.history.entry(index.0).or_default();
+        history.push_back(actions.to_mask());
+        while history.len() > latency + 1 {
+            history.pop_front();
+        }
+        let mut mask = history[0];
+
+        if inverted {
+            mask = swap_actions(mask, Action::MoveLeft, Action::MoveRight);
+        }
+        if state.jump_swapped {
+            mask = swap_actions(mask, Action::Jump, Action::MoveUp);
+        }
+
+        if mask != actions.to_mask() {
+            actions.set_mask(mask);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn default_stats() -> PlayerStats {
+        ron::from_str::<PlayerStats>(include_str!("../assets/potions/default_player.ron")).unwrap()
+    }
+
+    #[test]
+    fn modifiers_set_add_and_multiply() {
+        let mut stats = default_stats();
+        let modifiers = StatModifiers {
+            player_max_velocity: Some(Modifier::Set(225.0)),
+            jump_strength: Some(Modifier::Add(10.0)),
+            true_gravity: Some(Modifier::Mul(0.5)),
+            can_wall_jump: Some(true),
+            ..default()
+        };
+        modifiers.apply(&mut stats);
+
+        let base = default_stats();
+        assert_eq!(stats.player_max_velocity, 225.0);
+        assert_eq!(stats.jump_strength, base.jump_strength + 10.0);
+        assert_eq!(stats.true_gravity, base.true_gravity * 0.5);
+        assert!(stats.can_wall_jump);
+        assert_eq!(stats.player_accel, base.player_accel);
+    }
+
+    #[test]
+    fn air_jumps_round_and_stay_positive() {
+        let mut stats = default_stats();
+        StatModifiers {
+            air_jumps: Some(Modifier::Add(1.6)),
+            ..default()
+        }
+        .apply(&mut stats);
+        assert_eq!(stats.air_jumps, 2);
+
+        StatModifiers {
+            air_jumps: Some(Modifier::Add(-5.0)),
+            ..default()
+        }
+        .apply(&mut stats);
+        assert_eq!(stats.air_jumps, 0);
+    }
+}
@@ -0,0 +1,183 @@
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RunSeed>()
+            .add_system(start_run.in_schedule(OnExit(GameState::Menu)))
+            .add_systems(
+                (
+                    play_inputs.run_if(resource_exists::<ReplayPlayback>()),
+                    record_inputs.run_if(resource_exists::<ReplayRecorder>()),
+                )
+                    .chain()
+                    .in_set(ReplaySet)
+                    .after(update_tick_actions)
+                    .before(PlayerMovementSet)
+                    .distributive_run_if(simulation_running)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            );
+
+        //Runs quit before winning are kept too, the recorder is taken so a run is only saved once
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_system(save_replay.in_schedule(OnEnter(GameState::Win)))
+            .add_system(
+                save_replay
+                    .in_base_set(CoreSet::Last)
+                    .run_if(on_event::<bevy::app::AppExit>()),
+            );
+    }
+}
+
+/// Replay systems that read or overwrite `TickActions` before anything else changes them
+#[derive(SystemSet, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ReplaySet;
+
+pub const REPLAY_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Layout of `Replay::inputs`, replays in another format can't be played
+pub const REPLAY_FORMAT: u32 = 2;
+
+/// A run stored as the actions seen by each simulation tick
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: String,
+    /// Replays from before formats were tracked load as 0
+    #[serde(default)]
+    pub format: u32,
+    pub seed: u64,
+    /// Whether a second player was spawned, see `CoOpSettings`
+    #[serde(default)]
+    pub co_op: bool,
+    /// Run length encoded `(action mask, ticks)`, each player's `TickActions::to_mask` takes 32 bits
+    pub inputs: Vec<(u64, u32)>,
+}
+
+impl Replay {
+    pub fn load(path: &str) -> Result<Replay, String> {
+        let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let replay = ron::from_str::<Replay>(&text).map_err(|err| err.to_string())?;
+        if replay.format != REPLAY_FORMAT {
+            return Err(format!(
+                "Replay format {} is not supported, expected {}",
+                replay.format, REPLAY_FORMAT
+            ));
+        }
+        if replay.version != REPLAY_VERSION {
+            warn!(
+                "Replay was recorded on version {} but this is {}, it may desync",
+                replay.version, REPLAY_VERSION
+            );
+        }
+        Ok(replay)
+    }
+}
+
+/// Seeds gameplay randomness so replays play out the same way
+#[derive(Resource, Default)]
+pub struct RunSeed(pub u64);
+
+#[derive(Resource)]
+pub struct ReplayRecorder(pub Replay);
+
+/// Feeds a replay into `TickActions` in place of the player's input
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    pub replay: Replay,
+    entry: usize,
+    tick: u32,
+}
+
+impl ReplayPlayback {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            entry: 0,
+            tick: 0,
+        }
+    }
+}
+
+fn start_run(
+    mut commands: Commands,
+    mut seed: ResMut<RunSeed>,
+    co_op: Res<CoOpSettings>,
+    playback: Option<Res<ReplayPlayback>>,
+) {
+    if let Some(playback) = playback {
+        seed.0 = playback.replay.seed;
+        return;
+    }
+    seed.0 = rand::random();
+    commands.insert_resource(ReplayRecorder(Replay {
+        version: REPLAY_VERSION.to_string(),
+        format: REPLAY_FORMAT,
+        seed: seed.0,
+        co_op: co_op.enabled,
+        inputs: Vec::new(),
+    }));
+}
+
+fn play_inputs(
+    mut playback: ResMut<ReplayPlayback>,
+    mut players: Query<(&PlayerIndex, &mut TickActions)>,
+) {
+    let playback = &mut *playback;
+    let mask = match playback.replay.inputs.get(playback.entry) {
+        Some(&(mask, ticks)) => {
+            playback.tick += 1;
+            if playback.tick >= ticks {
+                playback.entry += 1;
+                playback.tick = 0;
+                if playback.entry == playback.replay.inputs.len() {
+                    info!("Replay finished");
+                }
+            }
+            mask
+        }
+        None => 0,
+    };
+    for (index, mut actions) in &mut players {
+        actions.set_mask((mask >> (32 * index.0)) as u32);
+    }
+}
+
+fn record_inputs(
+    mut recorder: ResMut<ReplayRecorder>,
+    players: Query<(&PlayerIndex, &TickActions)>,
+) {
+    let mask = players.iter().fold(0, |mask, (index, actions)| {
+        mask | (actions.to_mask() as u64) << (32 * index.0)
+    });
+    match recorder.0.inputs.last_mut() {
+        Some((last, ticks)) if *last == mask && *ticks < u32::MAX => *ticks += 1,
+        _ => recorder.0.inputs.push((mask, 1)),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save_replay(mut commands: Commands, recorder: Option<Res<ReplayRecorder>>) {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let Some(recorder) = recorder else {
+        return;
+    };
+    commands.remove_resource::<ReplayRecorder>();
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default();
+    let path = format!("replays/replay_{}.ron", time);
+    let result = ron::to_string(&recorder.0)
+        .map_err(|err| err.to_string())
+        .and_then(|text| {
+            std::fs::create_dir_all("replays").map_err(|err| err.to_string())?;
+            std::fs::write(&path, text).map_err(|err| err.to_string())
+        });
+    match result {
+        Ok(()) => info!("Saved replay to {}", path),
+        Err(err) => warn!("Failed to save replay {}: {}", path, err),
+    }
+}
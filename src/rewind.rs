@@ -0,0 +1,241 @@
+use std::collections::VecDeque;
+
+use crate::prelude::*;
+
+pub struct RewindPlugin;
+
+impl Plugin for RewindPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RewindSettings>()
+            .init_resource::<RewindState>()
+            .init_resource::<RewindBuffer>()
+            .add_system(
+                rewind
+                    .after(restore_physics_transforms)
+                    .after(update_tick_actions)
+                    .after(ReplaySet)
+                    .in_set(RewindSet)
+                    .before(PlayerMovementSet)
+                    .run_if(simulation_running)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(
+                record_snapshot
+                    .after(PlayerCheckSet)
+                    .run_if(simulation_running)
+                    .run_if(not_rewinding)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(show_rewind_effect);
+
+        //Nothing moves on its own while time runs backwards
+        app.edit_schedule(CoreSchedule::FixedUpdate, |schedule| {
+            schedule
+                .configure_set(PlayerMovementSet.run_if(not_rewinding))
+                .configure_set(PlayerCheckSet.run_if(not_rewinding));
+        });
+    }
+}
+
+/// Restores recorded snapshots, systems that move things on their own run after it
+#[derive(SystemSet, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct RewindSet;
+
+/// Seconds of rewind the assist option gives when the player's stats don't allow more
+pub const ASSIST_REWIND_TIME: f32 = 5.0;
+
+#[derive(Resource, Default)]
+pub struct RewindSettings {
+    /// Lets the player rewind without a potion
+    pub assist: bool,
+}
+
+#[derive(Resource, Default)]
+pub struct RewindState {
+    pub rewinding: bool,
+}
+
+/// Marks entities whose transform and velocity are recorded for rewinding
+#[derive(Component)]
+pub struct Rewindable;
+
+#[derive(Clone, Copy)]
+pub struct RewindFrame {
+    pub entity: Entity,
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub linvel: Vec2,
+    pub angvel: f32,
+}
+
+#[derive(Default)]
+pub struct Snapshot {
+    pub frames: Vec<RewindFrame>,
+    pub auto_scroll: Option<f32>,
+}
+
+/// One snapshot per simulation tick, oldest first
+#[derive(Resource, Default)]
+pub struct RewindBuffer {
+    pub snapshots: VecDeque<Snapshot>,
+    level: usize,
+}
+
+pub fn not_rewinding(state: Res<RewindState>) -> bool {
+    !state.rewinding
+}
+
+fn rewind_time(stats: &PlayerStats, settings: &RewindSettings) -> f32 {
+    if settings.assist {
+        stats.rewind_time.max(ASSIST_REWIND_TIME)
+    } else {
+        stats.rewind_time
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn rewind(
+    mut commands: Commands,
+    mut player: Query<(
+        &mut PlayerVelocity,
+        &PlayerStats,
+        &PlayerState,
+        &TickActions,
+    )>,
+    mut rewindables: Query<
+        (&mut Transform, Option<&mut Velocity>),
+        (With<Rewindable>, Without<PlayerVelocity>),
+    >,
+    mut player_transform: Query<&mut Transform, (With<Rewindable>, With<PlayerVelocity>)>,
+    mut controllers: Query<&mut KinematicCharacterController>,
+    mut waiting: Query<&mut Visibility, With<ReachedExit>>,
+    mut auto_scroll: Option<ResMut<AutoScroll>>,
+    mut buffer: ResMut<RewindBuffer>,
+    mut state: ResMut<RewindState>,
+    mut rapier: ResMut<RapierConfiguration>,
+    settings: Res<RewindSettings>,
+) {
+    //Any player can rewind, which takes everyone back with them
+    let wants_rewind = player.iter().any(|(_, stats, state, actions)| {
+        rewind_time(stats, &settings) > 0.0
+            && state.has_control()
+            && actions.pressed(Action::Rewind)
+    });
+
+    let snapshot = if wants_rewind {
+        buffer.snapshots.pop_back()
+    } else {
+        None
+    };
+
+    let rewinding = snapshot.is_some();
+    if rewinding != state.rewinding {
+        state.rewinding = rewinding;
+        //Crates would keep falling while they are put back otherwise
+        rapier.physics_pipeline_active = !rewinding;
+    }
+
+    if let Some(snapshot) = snapshot {
+        for frame in &snapshot.frames {
+            if let Ok((mut transform, velocity)) = rewindables.get_mut(frame.entity) {
+                transform.translation = frame.translation;
+                transform.rotation = frame.rotation;
+                if let Some(mut velocity) = velocity {
+                    velocity.linvel = frame.linvel;
+                    velocity.angvel = frame.angvel;
+                }
+            }
+            if let Ok(mut transform) = player_transform.get_mut(frame.entity) {
+                transform.translation = frame.translation;
+            }
+            //Players waiting at the door are taken back out of it
+            if let Ok(mut visibility) = waiting.get_mut(frame.entity) {
+                *visibility = Visibility::Inherited;
+                commands.entity(frame.entity).remove::<ReachedExit>();
+            }
+            if let Ok((mut velocity, _, _, _)) = player.get_mut(frame.entity) {
+                velocity.velocity = frame.linvel;
+                velocity.grapple = None;
+            }
+        }
+        if let (Some(auto_scroll), Some(distance)) = (auto_scroll.as_mut(), snapshot.auto_scroll) {
+            auto_scroll.distance = distance;
+        }
+        for mut controller in &mut controllers {
+            controller.translation = None;
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn record_snapshot(
+    rewindables: Query<
+        (
+            Entity,
+            &Transform,
+            Option<&Velocity>,
+            Option<&PlayerVelocity>,
+        ),
+        With<Rewindable>,
+    >,
+    player: Query<&PlayerStats>,
+    auto_scroll: Option<Res<AutoScroll>>,
+    progression: Res<StoryProgression>,
+    settings: Res<RewindSettings>,
+    mut respawns: EventReader<PlayerRespawnedEvent>,
+    mut buffer: ResMut<RewindBuffer>,
+    time: Res<FixedTime>,
+) {
+    //History from before a death or another level can't be returned to
+    if respawns.iter().count() > 0 || buffer.level != progression.current_map {
+        buffer.level = progression.current_map;
+        buffer.snapshots.clear();
+    }
+
+    let seconds = player
+        .iter()
+        .map(|stats| rewind_time(stats, &settings))
+        .fold(0.0, f32::max);
+    let capacity = (seconds / time.period.as_secs_f32()) as usize;
+    if capacity == 0 {
+        buffer.snapshots.clear();
+        return;
+    }
+
+    let frames = rewindables
+        .iter()
+        .map(|(entity, transform, velocity, player)| RewindFrame {
+            entity,
+            translation: transform.translation,
+            rotation: transform.rotation,
+            linvel: player
+                .map(|player| player.velocity)
+                .or(velocity.map(|velocity| velocity.linvel))
+                .unwrap_or_default(),
+            angvel: velocity.map(|velocity| velocity.angvel).unwrap_or_default(),
+        })
+        .collect();
+    buffer.snapshots.push_back(Snapshot {
+        frames,
+        auto_scroll: auto_scroll.map(|auto_scroll| auto_scroll.distance),
+    });
+    while buffer.snapshots.len() > capacity {
+        buffer.snapshots.pop_front();
+    }
+}
+
+fn show_rewind_effect(
+    state: Res<RewindState>,
+    mut effect: Query<&mut Visibility, With<Handle<RewindMaterial>>>,
+) {
+    if !state.is_changed() {
+        return;
+    }
+    for mut visibility in &mut effect {
+        *visibility = if state.rewinding {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
@@ -0,0 +1,140 @@
+use std::time::Duration;
+
+use bevy::transform::TransformSystem;
+use bevy_rapier2d::plugin::systems::{init_colliders, init_rigid_bodies, sync_removals};
+
+use crate::prelude::*;
+
+pub const SIMULATION_DT: f32 = 1.0 / 60.0;
+
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(FixedTime::new(Duration::from_secs_f32(SIMULATION_DT)))
+            .add_startup_system(configure_physics)
+            .add_system(
+                restore_physics_transforms
+                    .before(PlayerMovementSet)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(
+                record_physics_transforms
+                    .after(PhysicsSet::Writeback)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(
+                interpolate_physics_transforms
+                    .in_base_set(CoreSet::PostUpdate)
+                    .before(TransformSystem::TransformPropagate),
+            )
+            //Removals are only kept for two frames, which fast frames can outrun between ticks
+            .add_systems(
+                (init_rigid_bodies, init_colliders, sync_removals)
+                    .chain()
+                    .in_base_set(CoreSet::PostUpdate)
+                    .after(TransformSystem::TransformPropagate),
+            );
+
+        //Physics steps with the player so movement is frame rate independent, and pauses with it
+        app.edit_schedule(CoreSchedule::FixedUpdate, |schedule| {
+            schedule.configure_sets(
+                (
+                    PhysicsSet::SyncBackend,
+                    PhysicsSet::SyncBackendFlush,
+                    PhysicsSet::StepSimulation,
+                    PhysicsSet::Writeback,
+                )
+                    .chain(),
+            );
+            for set in [
+                PhysicsSet::SyncBackend,
+                PhysicsSet::SyncBackendFlush,
+                PhysicsSet::StepSimulation,
+                PhysicsSet::Writeback,
+            ] {
+                schedule.configure_set(set.clone().run_if(simulation_running));
+                schedule.add_systems(
+                    RapierPhysicsPlugin::<NoUserData>::get_systems(set.clone()).in_base_set(set),
+                );
+            }
+        });
+    }
+}
+
+/// The simulation only advances while platforming and stops as soon as a state change is queued
+pub fn simulation_running(
+    state: Res<State<GameState>>,
+    next_state: Res<NextState<GameState>>,
+) -> bool {
+    state.0 == GameState::Platforming && next_state.0.is_none()
+}
+
+/// Smooths rendering of entities moved in the fixed timestep.
+/// `rendered` is compared against the transform to detect teleports from outside the simulation.
+#[derive(Component)]
+pub struct PhysicsInterpolation {
+    pub previous: Vec3,
+    pub current: Vec3,
+    pub rendered: Vec3,
+}
+
+impl PhysicsInterpolation {
+    pub fn new(position: Vec3) -> Self {
+        Self {
+            previous: position,
+            current: position,
+            rendered: position,
+        }
+    }
+
+    fn snap(&mut self, position: Vec3) {
+        *self = Self::new(position);
+    }
+}
+
+fn configure_physics(mut config: ResMut<RapierConfiguration>) {
+    config.timestep_mode = TimestepMode::Fixed {
+        dt: SIMULATION_DT,
+        substeps: 1,
+    };
+}
+
+pub fn restore_physics_transforms(
+    mut interpolated: Query<(&mut Transform, &mut PhysicsInterpolation)>,
+) {
+    for (mut transform, mut interpolation) in &mut interpolated {
+        if transform.translation != interpolation.rendered {
+            interpolation.snap(transform.translation);
+        } else {
+            transform.translation = interpolation.current;
+            interpolation.rendered = interpolation.current;
+        }
+    }
+}
+
+fn record_physics_transforms(mut interpolated: Query<(&Transform, &mut PhysicsInterpolation)>) {
+    for (transform, mut interpolation) in &mut interpolated {
+        interpolation.previous = interpolation.current;
+        interpolation.current = transform.translation;
+        interpolation.rendered = transform.translation;
+    }
+}
+
+fn interpolate_physics_transforms(
+    mut interpolated: Query<(&mut Transform, &mut PhysicsInterpolation)>,
+    fixed_time: Res<FixedTime>,
+) {
+    let alpha = fixed_time.accumulated().as_secs_f32() / fixed_time.period.as_secs_f32();
+    for (mut transform, mut interpolation) in &mut interpolated {
+        if transform.translation != interpolation.rendered {
+            interpolation.snap(transform.translation);
+            continue;
+        }
+        let rendered = interpolation
+            .previous
+            .lerp(interpolation.current, alpha.clamp(0.0, 1.0));
+        transform.translation = rendered;
+        interpolation.rendered = rendered;
+    }
+}
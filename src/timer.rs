@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use bevy::time::Stopwatch;
 
 use crate::prelude::*;
@@ -8,6 +10,15 @@ impl Plugin for SpeedrunPlugin {
     fn build(&self, app: &mut App) {
         app.add_system(spawn_timer_ui.in_schedule(OnExit(GameState::Menu)))
             .add_system(stop_timer.in_schedule(OnEnter(GameState::Win)))
+            .init_resource::<LevelSplits>()
+            .add_event::<LevelSplitEvent>()
+            .add_systems(
+                (tick_timer, update_splits)
+                    .chain()
+                    .after(PlayerCheckSet)
+                    .distributive_run_if(simulation_running)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
             .add_system(update_timer_ui.in_set(OnUpdate(GameState::Platforming)));
     }
 }
@@ -15,7 +26,25 @@ impl Plugin for SpeedrunPlugin {
 #[derive(Component)]
 pub struct TimerUI(pub Stopwatch);
 
-fn stop_timer(mut ui: Query<(&mut Text, &mut TimerUI)>) {
+/// A level starts when its map loads and is split when the player reaches the door
+#[derive(Resource, Default)]
+pub struct LevelSplits {
+    pub level: usize,
+    pub level_start: Duration,
+    /// Time taken by each finished level
+    pub splits: Vec<Duration>,
+    pub level_deaths: usize,
+    /// Deaths over the whole run
+    pub deaths: usize,
+}
+
+pub struct LevelSplitEvent {
+    pub level: usize,
+    pub time: Duration,
+}
+
+fn stop_timer(mut ui: Query<(&mut Text, &mut TimerUI)>, splits: Res<LevelSplits>) {
+    info!("Finished with {} deaths", splits.deaths);
     for (mut text, _) in &mut ui {
         text.sections[0].value = "".to_string();
     }
@@ -57,9 +86,50 @@ fn spawn_timer_ui(mut commands: Commands, assets: Res<AssetServer>) {
     });
 }
 
-fn update_timer_ui(mut ui: Query<(&mut Text, &mut TimerUI)>, time: Res<Time>) {
-    for (mut text, mut timer) in &mut ui {
-        timer.0.tick(time.delta());
+//Ticked with the simulation so times don't depend on frame rate
+fn tick_timer(mut timers: Query<&mut TimerUI>, time: Res<FixedTime>) {
+    for mut timer in &mut timers {
+        timer.0.tick(time.period);
+    }
+}
+
+pub fn update_splits(
+    timers: Query<&TimerUI>,
+    mut exits: EventReader<LevelExitedEvent>,
+    mut deaths: EventReader<PlayerDiedEvent>,
+    progression: Res<StoryProgression>,
+    mut splits: ResMut<LevelSplits>,
+    mut writer: EventWriter<LevelSplitEvent>,
+) {
+    if let Ok(timer) = timers.get_single() {
+        let now = timer.0.elapsed();
+        if splits.level != progression.current_map {
+            splits.level = progression.current_map;
+            splits.level_start = now;
+            splits.level_deaths = 0;
+        }
+        let died = deaths.iter().count();
+        splits.level_deaths += died;
+        splits.deaths += died;
+        if exits.iter().count() > 0 {
+            let time = now - splits.level_start;
+            info!(
+                "Level {} split {:0.2}s with {} deaths",
+                splits.level + 1,
+                time.as_secs_f32(),
+                splits.level_deaths
+            );
+            splits.splits.push(time);
+            writer.send(LevelSplitEvent {
+                level: splits.level,
+                time,
+            });
+        }
+    }
+}
+
+fn update_timer_ui(mut ui: Query<(&mut Text, &TimerUI)>) {
+    for (mut text, timer) in &mut ui {
         text.sections[0].value = format!("{:0.2}s", timer.0.elapsed().as_secs_f32());
     }
 }